@@ -3,6 +3,8 @@ use serde::{Deserialize, Serialize};
 use std::{error::Error, fmt};
 
 use crate::end_of_life;
+use crate::runtime::{self, Stream};
+use crate::version::{Requirement, Version};
 
 #[derive(Debug)]
 pub struct RegexCaptureError;
@@ -13,34 +15,200 @@ impl fmt::Display for RegexCaptureError {
 	}
 }
 
+/// A command to run inside a container
+///
+/// Either an argument list, run as-is, or a command line which is run
+/// through the container's shell so that pipes, quoting and redirects work.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum VersionCommand {
+	/// An argument list, e.g., `["bash", "--version"]`
+	Args(Vec<String>),
+	/// A shell command line, e.g., `"dpkg -s openssl | grep Version"`
+	Shell(String),
+}
+
+impl VersionCommand {
+	/// The argument list to execute inside a container
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::application::VersionCommand;
+	/// let command = VersionCommand::from("dpkg -s openssl | grep Version");
+	/// assert_eq!(command.argv(), ["sh", "-c", "dpkg -s openssl | grep Version"]);
+	///
+	/// let command = VersionCommand::Args(vec![String::from("bash"), String::from("--version")]);
+	/// assert_eq!(command.argv(), ["bash", "--version"]);
+	/// ```
+	pub fn argv(&self) -> Vec<String> {
+		match self {
+			Self::Args(x) => x.clone(),
+			Self::Shell(x) => vec![String::from("sh"), String::from("-c"), x.clone()],
+		}
+	}
+}
+
+impl From<&str> for VersionCommand {
+	fn from(value: &str) -> Self {
+		Self::Shell(String::from(value))
+	}
+}
+
+impl fmt::Display for VersionCommand {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Args(x) => write!(f, "{x:?}"),
+			Self::Shell(x) => write!(f, "{x}"),
+		}
+	}
+}
+
+/// Limits on which versions of an app may be found
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Constraints {
+	/// The oldest version allowed, e.g., `3.0`
+	pub minimum: Option<Version>,
+
+	/// Comparisons every version must satisfy, e.g., `>=1.24, <2`
+	pub allowed: Option<Requirement>,
+}
+
+impl Constraints {
+	/// Check a version against these constraints, if there are any
+	///
+	/// An unknown or unparseable version fails, since it can't be shown to be
+	/// allowed.
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::application::{Check, Constraints};
+	/// let constraints = Constraints {
+	///     minimum: "3.0".parse().ok(),
+	///     ..Default::default()
+	/// };
+	/// let fail = |reason: &str| Some(Check::Fail { reason: String::from(reason) });
+	///
+	/// assert_eq!(constraints.check(Some("3.0.2")), Some(Check::Pass));
+	/// assert_eq!(constraints.check(Some("1.1.1w")), fail("1.1.1w is below minimum 3.0"));
+	/// assert_eq!(constraints.check(Some("stable")), fail("unparseable version `stable`"));
+	/// assert_eq!(constraints.check(None), fail("version is unknown"));
+	/// assert_eq!(Constraints::default().check(None), None);
+	/// ```
+	pub fn check(&self, version: Option<&str>) -> Option<Check> {
+		if self.minimum.is_none() && self.allowed.is_none() {
+			return None;
+		}
+
+		let fail = |reason: String| Some(Check::Fail { reason });
+		let Some(raw) = version else {
+			return fail(String::from("version is unknown"));
+		};
+		let Ok(version) = raw.parse::<Version>() else {
+			return fail(format!("unparseable version `{raw}`"));
+		};
+
+		if let Some(x) = self
+			.minimum
+			.as_ref()
+			.filter(|x| version.cmp_bound(x).is_lt())
+		{
+			return fail(format!("{version} is below minimum {x}"));
+		}
+		if let Some(x) = self.allowed.as_ref().filter(|x| !x.matches(&version)) {
+			return fail(format!("{version} is not allowed by \"{x}\""));
+		}
+
+		Some(Check::Pass)
+	}
+}
+
+/// Whether a version met its app's constraints
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Check {
+	Pass,
+	Fail { reason: String },
+}
+
 /// Configuration details for an application
 ///
 /// A representation of a corrator.toml config provided by a user.
 #[derive(Serialize, Deserialize)]
 pub struct Application {
-	/// A regex pattern for pulling version numbers from command output
-	#[serde(with = "serde_regex")]
+	/// A regex pattern for pulling version numbers from command output;
+	/// defaults to the whole output, which suits package versions
+	#[serde(with = "serde_regex", default = "whole_version")]
 	pub version_regex: Regex,
 
 	/// The command to run to determine current version, e.g., `bash --version`
-	pub version_command: String,
+	#[serde(default)]
+	pub version_command: Option<VersionCommand>,
+
+	/// A file to read the current version from instead of running a command,
+	/// e.g., `/etc/os-release`; this works on images without a shell
+	pub version_file: Option<String>,
+
+	/// An installed package to read the version of from dpkg, apk or rpm,
+	/// whichever the container has, e.g., `openssl`
+	pub package: Option<String>,
+
+	/// Which output stream(s) of version_command to match version_regex against
+	#[serde(default)]
+	pub version_stream: Stream,
+
+	/// Seconds the version command may run for, overriding the exec timeout
+	#[serde(default, deserialize_with = "runtime::seconds")]
+	pub timeout: Option<u64>,
 
 	/// An optional endoflife.date config
 	pub eol: Option<end_of_life::EolConfig>,
+
+	/// Versions this app may be found at; containers may override these
+	#[serde(flatten)]
+	pub constraints: Constraints,
+}
+
+fn whole_version() -> Regex {
+	Regex::new(r"(?P<version>.+)").unwrap()
 }
 
 impl Application {
+	/// Describe a failure to query this application's version
+	pub fn failure(&self, kind: FailureKind) -> Failure {
+		let failure = Failure {
+			regex: Some(String::from(self.version_regex.as_str())),
+			..Failure::new(kind)
+		};
+
+		// Only the probe which was used, in the order they're tried
+		match (&self.version_file, &self.package, &self.version_command) {
+			(Some(x), _, _) => Failure {
+				file: Some(x.clone()),
+				..failure
+			},
+			(None, Some(x), _) => Failure {
+				package: Some(x.clone()),
+				..failure
+			},
+			(None, None, x) => Failure {
+				command: x.as_ref().map(|x| x.to_string()),
+				..failure
+			},
+		}
+	}
+
 	/// Given the output of version_command, retrieve the version number via regex
 	///
 	/// # Example
 	/// ```rust
 	/// # use std::error::Error;
 	/// # use regex::Regex;
+	/// # use corrator::application::VersionCommand;
 	/// # fn main() -> Result<(), Box<dyn Error>> {
 	/// let application = corrator::application::Application {
 	///     version_regex: Regex::new(r"test: (?P<version>[0-9.]+)")?,
-	///     version_command: String::from(""),
-	///     eol: None,
+	///     version_command: Some(VersionCommand::from("test --version")),
+	///     ..Default::default()
 	/// };
 	///
 	/// let version = application.query_version("test: 1.2.3")?;
@@ -66,21 +234,194 @@ impl Default for Application {
 	fn default() -> Self {
 		Self {
 			version_regex: Regex::new(r"test: (?P<version>[0-9.]+)").unwrap(),
-			version_command: String::from(""),
+			version_command: Some(VersionCommand::from("")),
+			version_file: None,
+			package: None,
+			version_stream: Default::default(),
+			timeout: None,
 			eol: None,
+			constraints: Constraints::default(),
 		}
 	}
 }
 
+/// The kind of problem which stopped a version from being found
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+	/// The app is listed on a container but has no configuration
+	Undefined,
+	/// The runtime was unable to run the version command, read the version
+	/// file or query the package
+	Execution,
+	/// The version command exited unsuccessfully
+	NonZeroExit,
+	/// The version command needs a shell, but the container is probed by files
+	NoShell,
+	/// The app has no version command, version file or package
+	NoProbe,
+	/// The version regex didn't match the version command's output
+	NoMatch,
+	/// The version command or file read outlasted its timeout
+	Timeout,
+	/// The container has none of the supported package managers
+	NoPackageManager,
+	/// The package manager doesn't list the package as installed
+	NotInstalled,
+}
+
+impl fmt::Display for FailureKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Undefined => write!(f, "app is not defined"),
+			Self::Execution => write!(f, "version query failed"),
+			Self::NonZeroExit => write!(f, "version command exited unsuccessfully"),
+			Self::NoShell => write!(f, "version command needs a shell"),
+			Self::NoProbe => write!(f, "no version command, file or package"),
+			Self::NoMatch => write!(f, "version regex did not match"),
+			Self::Timeout => write!(f, "version query timed out"),
+			Self::NoPackageManager => write!(f, "no supported package manager"),
+			Self::NotInstalled => write!(f, "package is not installed"),
+		}
+	}
+}
+
+/// Details of a failed version query, kept for troubleshooting
+#[derive(Serialize, Debug, Clone)]
+pub struct Failure {
+	pub kind: FailureKind,
+	pub command: Option<String>,
+	pub file: Option<String>,
+	pub package: Option<String>,
+	pub output: Option<String>,
+	pub regex: Option<String>,
+
+	/// The version command's exit code, if it ran
+	pub exit_code: Option<i32>,
+
+	/// Any further detail, e.g., the error reported by the runtime
+	pub message: Option<String>,
+}
+
+/// Names the probe which failed, where the kind alone can't
+///
+/// # Example
+/// ```rust
+/// # use corrator::application::{Failure, FailureKind};
+/// let failure = Failure {
+///     file: Some(String::from("/etc/os-release")),
+///     ..Failure::new(FailureKind::Timeout)
+/// };
+///
+/// assert_eq!(failure.to_string(), "reading file `/etc/os-release` timed out");
+/// assert_eq!(Failure::new(FailureKind::Timeout).to_string(), "version command timed out");
+/// ```
+impl fmt::Display for Failure {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let probe = match (&self.file, &self.package) {
+			(Some(x), _) => format!("reading file `{x}`"),
+			(None, Some(x)) => format!("querying package `{x}`"),
+			(None, None) => String::from("version command"),
+		};
+
+		match (&self.kind, &self.package) {
+			(FailureKind::Execution, _) => write!(f, "{probe} failed"),
+			(FailureKind::Timeout, _) => write!(f, "{probe} timed out"),
+			(FailureKind::NotInstalled, Some(x)) => write!(f, "package `{x}` is not installed"),
+			(kind, _) => write!(f, "{kind}"),
+		}
+	}
+}
+
+impl Failure {
+	pub fn new(kind: FailureKind) -> Self {
+		Self {
+			kind,
+			command: None,
+			file: None,
+			package: None,
+			output: None,
+			regex: None,
+			exit_code: None,
+			message: None,
+		}
+	}
+
+	pub fn with_output(mut self, output: String) -> Self {
+		self.output = Some(output);
+		self
+	}
+
+	pub fn with_exit_code(mut self, exit_code: Option<i32>) -> Self {
+		self.exit_code = exit_code;
+		self
+	}
+
+	pub fn with_message(mut self, message: String) -> Self {
+		self.message = Some(message);
+		self
+	}
+}
+
 /// The currency status of an application
 ///
-/// Contains the current version and an optional endoflife.date response
-#[derive(Serialize, Debug)]
+/// Contains the current version and an optional endoflife.date response,
+/// or the reason a version couldn't be found.
+#[derive(Serialize, Debug, Clone)]
 pub struct Status {
 	pub name: String,
-	pub version: String,
+	pub version: Option<String>,
+
+	/// The version, parsed so that it can be compared; left out of output
+	/// when the version couldn't be parsed
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub parsed_version: Option<Version>,
 
 	/// A parsed output from endoflife.date; either a date representing
 	/// the end of support, or "alive" if no date has been set
 	pub eol_status: Option<String>,
+
+	/// Why the version is unknown, if the query failed
+	pub failure: Option<Failure>,
+
+	/// How many attempts the version command or file read took
+	pub attempts: u32,
+
+	/// Whether the version met its constraints, if it has any
+	pub check: Option<Check>,
+}
+
+impl Status {
+	/// # Example
+	/// ```rust
+	/// # use corrator::application::Status;
+	/// let bash = Status::new(String::from("bash"), String::from("5.2.15"), None);
+	/// let app = Status::new(String::from("app"), String::from("unknown"), None);
+	///
+	/// assert_eq!(serde_json::to_value(bash).unwrap()["parsed_version"], "5.2.15");
+	/// assert_eq!(serde_json::to_value(app).unwrap().get("parsed_version"), None);
+	/// ```
+	pub fn new(name: String, version: String, eol_status: Option<String>) -> Self {
+		Self {
+			name,
+			parsed_version: version.parse().ok(),
+			version: Some(version),
+			eol_status,
+			failure: None,
+			attempts: 0,
+			check: None,
+		}
+	}
+
+	pub fn failed(name: String, failure: Failure) -> Self {
+		Self {
+			name,
+			version: None,
+			parsed_version: None,
+			eol_status: None,
+			failure: Some(failure),
+			attempts: 0,
+			check: None,
+		}
+	}
 }
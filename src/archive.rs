@@ -0,0 +1,751 @@
+use flate2::read::GzDecoder;
+use serde_json::Value;
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, HashSet},
+	fmt::Display,
+	fs::File,
+	io::{self, BufRead, BufReader, Read},
+	path::{Component, Path, PathBuf},
+	time::Duration,
+};
+
+use crate::docker::DockerError;
+use crate::runtime::{self, ContainerRuntime, ExecOutput};
+
+const MAX_SYMLINKS: usize = 40;
+
+/// An image stored on disk, to be inspected without a container runtime
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+	/// An OCI image layout directory, e.g., `oci:./images/ubuntu`
+	Oci(PathBuf),
+	/// A tarball created by `docker save`, e.g., `docker-archive:./ubuntu.tar`
+	DockerArchive(PathBuf),
+}
+
+impl Source {
+	/// Parse a container path with an `oci:` or `docker-archive:` prefix
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::archive::Source;
+	/// # use std::path::PathBuf;
+	/// assert_eq!(
+	///     Source::parse("docker-archive:/tmp/ubuntu.tar"),
+	///     Some(Source::DockerArchive(PathBuf::from("/tmp/ubuntu.tar")))
+	/// );
+	/// assert_eq!(Source::parse("ubuntu"), None);
+	/// ```
+	pub fn parse(path: &str) -> Option<Self> {
+		let expand = |x: &str| PathBuf::from(shellexpand::tilde(x).into_owned());
+
+		if let Some(x) = path.strip_prefix("oci:") {
+			Some(Self::Oci(expand(x)))
+		} else {
+			path.strip_prefix("docker-archive:")
+				.map(|x| Self::DockerArchive(expand(x)))
+		}
+	}
+
+	fn path(&self) -> &Path {
+		match self {
+			Self::Oci(x) | Self::DockerArchive(x) => x,
+		}
+	}
+
+	/// Read a whole file, relative to the layout directory or tarball root
+	fn read(&self, name: &str) -> Result<Vec<u8>, DockerError> {
+		let mut contents = vec![];
+		self.with_file(name, |x| x.read_to_end(&mut contents).map(|_| ()))?;
+		Ok(contents)
+	}
+
+	fn read_json(&self, name: &str) -> Result<Value, DockerError> {
+		serde_json::from_slice(&self.read(name)?).map_err(invalid)
+	}
+
+	/// Stream a file, relative to the layout directory or tarball root
+	fn with_file(
+		&self,
+		name: &str,
+		f: impl FnOnce(&mut dyn Read) -> io::Result<()>,
+	) -> Result<(), DockerError> {
+		match self {
+			Self::Oci(x) => f(&mut File::open(x.join(name))?)?,
+			Self::DockerArchive(x) => {
+				let mut archive = tar::Archive::new(File::open(x)?);
+				let wanted = normalize(Path::new(name));
+
+				for entry in archive.entries()? {
+					let mut entry = entry?;
+					if normalize(&entry.path()?) == wanted {
+						return Ok(f(&mut entry)?);
+					}
+				}
+
+				return Err(invalid(format!("{name} is missing from {}", x.display())));
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// A file in the merged image filesystem
+#[derive(Debug, Clone)]
+enum Node {
+	/// Contents are stored at this path in a layer
+	File {
+		layer: usize,
+		path: PathBuf,
+	},
+	Symlink(PathBuf),
+	Directory,
+}
+
+/// An image's merged filesystem, indexed without holding file contents
+#[derive(Debug, Default)]
+struct Image {
+	/// Layer blobs, from the bottom up, relative to the source root
+	layers: Vec<String>,
+	files: BTreeMap<PathBuf, Node>,
+}
+
+impl Image {
+	fn load(source: &Source, platform: Option<&str>) -> Result<Self, DockerError> {
+		if !source.path().exists() {
+			return Err(DockerError::ImageNotFound(
+				source.path().display().to_string(),
+			));
+		}
+
+		let mut image = Self {
+			layers: layers(source, platform)?,
+			..Default::default()
+		};
+
+		for layer in 0..image.layers.len() {
+			image.apply_layer(source, layer)?;
+		}
+
+		Ok(image)
+	}
+
+	/// Merge a layer into the filesystem, honouring OCI whiteouts
+	fn apply_layer(&mut self, source: &Source, layer: usize) -> Result<(), DockerError> {
+		let mut added = HashSet::new();
+		let files = &mut self.files;
+
+		with_layer(source, &self.layers[layer], |archive| {
+			for entry in archive.entries()? {
+				let entry = entry?;
+				let path = normalize(&entry.path()?);
+				let name = path.file_name().map(|x| x.to_string_lossy().into_owned());
+				let parent = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+
+				// Whiteouts hide files from lower layers, never from this one
+				match name.as_deref() {
+					Some(".wh..wh..opq") => {
+						remove(files, &added, &parent, false);
+						continue;
+					}
+					Some(x) if x.starts_with(".wh.") => {
+						remove(files, &added, &parent.join(&x[4..]), true);
+						continue;
+					}
+					_ => (),
+				}
+
+				let kind = entry.header().entry_type();
+				let node = if kind.is_dir() {
+					Node::Directory
+				} else if kind.is_symlink() {
+					Node::Symlink(entry.link_name()?.unwrap_or_default().into_owned())
+				} else if kind.is_hard_link() {
+					let target = normalize(&entry.link_name()?.unwrap_or_default());
+					match files.get(&target) {
+						Some(x) => x.clone(),
+						None => continue,
+					}
+				} else if kind.is_file() {
+					Node::File {
+						layer,
+						path: path.clone(),
+					}
+				} else {
+					continue;
+				};
+
+				added.insert(path.clone());
+				files.insert(path, node);
+			}
+
+			Ok(())
+		})
+	}
+
+	/// Find where a file's contents live, following symlinks along the way
+	fn resolve(&self, path: &str) -> Result<(usize, PathBuf), DockerError> {
+		let not_found = || DockerError::FileNotFound(String::from(path));
+		let mut pending: Vec<PathBuf> = components(Path::new(path));
+		let mut resolved = PathBuf::from("/");
+		let mut hops = 0;
+
+		pending.reverse();
+		while let Some(component) = pending.pop() {
+			let candidate = resolved.join(component);
+
+			match self.files.get(&candidate) {
+				Some(Node::Symlink(target)) => {
+					hops += 1;
+					if hops > MAX_SYMLINKS {
+						return Err(not_found());
+					}
+
+					let target = match target.is_absolute() {
+						true => target.clone(),
+						false => resolved.join(target),
+					};
+					let mut target = components(&normalize(&target));
+					target.reverse();
+					pending.extend(target);
+					resolved = PathBuf::from("/");
+				}
+				// Parent directories aren't always listed in layers
+				_ => resolved = candidate,
+			}
+		}
+
+		match self.files.get(&resolved) {
+			Some(Node::File { layer, path }) => Ok((*layer, path.clone())),
+			_ => Err(not_found()),
+		}
+	}
+
+	fn read(&self, source: &Source, path: &str) -> Result<Vec<u8>, DockerError> {
+		let (layer, path) = self.resolve(path)?;
+		let mut contents = None;
+
+		with_layer(source, &self.layers[layer], |archive| {
+			for entry in archive.entries()? {
+				let mut entry = entry?;
+				if normalize(&entry.path()?) == path {
+					let mut data = vec![];
+					entry.read_to_end(&mut data)?;
+					contents = Some(data);
+					break;
+				}
+			}
+
+			Ok(())
+		})?;
+
+		contents.ok_or_else(|| DockerError::FileNotFound(path.display().to_string()))
+	}
+
+	/// Read every regular file directly inside a directory
+	fn read_dir(&self, source: &Source, path: &str) -> Result<Vec<Vec<u8>>, DockerError> {
+		let dir = normalize(Path::new(path));
+		let files: Vec<&PathBuf> = self
+			.files
+			.iter()
+			.filter(|(x, node)| x.parent() == Some(&dir) && matches!(node, Node::File { .. }))
+			.map(|(x, _)| x)
+			.collect();
+
+		if files.is_empty() && !matches!(self.files.get(&dir), Some(Node::Directory)) {
+			return Err(DockerError::FileNotFound(String::from(path)));
+		}
+
+		files
+			.into_iter()
+			.map(|x| self.read(source, &x.to_string_lossy()))
+			.collect()
+	}
+}
+
+/// Remove a path (or only its descendants) unless it was added by the current layer
+fn remove(
+	files: &mut BTreeMap<PathBuf, Node>,
+	added: &HashSet<PathBuf>,
+	path: &Path,
+	inclusive: bool,
+) {
+	let doomed: Vec<PathBuf> = files
+		.range(path.to_path_buf()..)
+		.map(|(x, _)| x)
+		.take_while(|x| x.starts_with(path))
+		.filter(|x| (inclusive || *x != path) && !added.contains(*x))
+		.cloned()
+		.collect();
+
+	for x in doomed {
+		files.remove(&x);
+	}
+}
+
+/// Open a layer blob as a tar archive, decompressing it if needed
+fn with_layer(
+	source: &Source,
+	layer: &str,
+	f: impl FnOnce(&mut tar::Archive<Box<dyn Read + '_>>) -> io::Result<()>,
+) -> Result<(), DockerError> {
+	source.with_file(layer, |reader| {
+		let mut reader = BufReader::new(reader);
+		let magic = reader.fill_buf()?;
+
+		let reader: Box<dyn Read> = if magic.starts_with(&[0x1f, 0x8b]) {
+			Box::new(GzDecoder::new(reader))
+		} else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+			return Err(io::Error::other("zstd compressed layers are not supported"));
+		} else {
+			Box::new(reader)
+		};
+
+		f(&mut tar::Archive::new(reader))
+	})
+}
+
+/// The layer blobs of an image, from the bottom up
+fn layers(source: &Source, platform: Option<&str>) -> Result<Vec<String>, DockerError> {
+	let layers = match source {
+		Source::DockerArchive(_) => {
+			let manifest = source.read_json("manifest.json")?;
+
+			// A docker-save tarball only ever holds a single platform
+			if let Some(platform) = platform {
+				let config = manifest[0]["Config"]
+					.as_str()
+					.ok_or_else(|| invalid("Image manifest has no config"))?;
+				if !matches_platform(&source.read_json(config)?, platform) {
+					return Err(DockerError::ImageNotFound(format!(
+						"{} for {platform}",
+						source.path().display()
+					)));
+				}
+			}
+
+			manifest[0]["Layers"].as_array().cloned()
+		}
+		Source::Oci(_) => {
+			let (_, manifest) = oci_manifest(source, platform)?;
+			manifest["layers"].as_array().map(|x| {
+				x.iter()
+					.filter_map(|x| x["digest"].as_str())
+					.map(|x| Value::from(blob(x)))
+					.collect()
+			})
+		}
+	};
+
+	layers
+		.ok_or_else(|| invalid("Image manifest has no layers"))?
+		.iter()
+		.map(|x| {
+			x.as_str()
+				.map(String::from)
+				.ok_or_else(|| invalid("Image manifest has an invalid layer"))
+		})
+		.collect()
+}
+
+/// Find the image manifest in an OCI layout, descending through indexes
+///
+/// Also returns the digest the layout's index refers to the image by, which
+/// is the one a registry would report.
+fn oci_manifest(
+	source: &Source,
+	platform: Option<&str>,
+) -> Result<(Option<String>, Value), DockerError> {
+	let mut document = source.read_json("index.json")?;
+	let mut image_digest = None;
+
+	for _ in 0..8 {
+		let descriptor = match document["manifests"].as_array() {
+			Some(x) => select(x, platform).ok_or_else(|| {
+				DockerError::ImageNotFound(format!(
+					"{} for {}",
+					source.path().display(),
+					platform.unwrap_or("any platform")
+				))
+			})?,
+			None => return Ok((image_digest, document)),
+		};
+
+		let digest = descriptor["digest"]
+			.as_str()
+			.ok_or_else(|| invalid("Image index has an invalid manifest"))?;
+		image_digest.get_or_insert_with(|| String::from(digest));
+		document = source.read_json(&blob(digest))?;
+	}
+
+	Err(invalid("Image indexes are nested too deeply"))
+}
+
+/// Pick a manifest from an index, skipping attestations and other non-images
+///
+/// Descriptors without a platform are nested indexes or single platform
+/// images, so they're always candidates.
+fn select<'a>(manifests: &'a [Value], platform: Option<&str>) -> Option<&'a Value> {
+	manifests.iter().find(|x| {
+		let descriptor = &x["platform"];
+		match (descriptor.is_null(), platform) {
+			(true, _) => true,
+			(false, Some(platform)) => matches_platform(descriptor, platform),
+			(false, None) => descriptor["os"].as_str() != Some("unknown"),
+		}
+	})
+}
+
+/// Whether an OCI platform object (or image config) matches e.g. "linux/arm64/v8"
+fn matches_platform(value: &Value, platform: &str) -> bool {
+	let mut wanted = platform.split('/');
+	let os = wanted.next();
+	let architecture = wanted.next();
+	let variant = wanted.next();
+
+	value["os"].as_str() == os
+		&& value["architecture"].as_str() == architecture
+		&& (variant.is_none() || value["variant"].as_str() == variant)
+}
+
+/// Describe an image from its manifest and config
+fn inspect(source: &Source, platform: Option<&str>) -> Result<runtime::Image, DockerError> {
+	let text = |x: &Value| x.as_str().map(String::from);
+
+	let (digest, id, size, config) = match source {
+		Source::DockerArchive(_) => {
+			let manifest = source.read_json("manifest.json")?;
+			let config = manifest[0]["Config"]
+				.as_str()
+				.ok_or_else(|| invalid("Image manifest has no config"))?;
+			// Configs are named after their digest, e.g., "blobs/sha256/<hex>" or "<hex>.json"
+			let name = Path::new(config)
+				.file_name()
+				.map(|x| x.to_string_lossy().trim_end_matches(".json").to_string())
+				.unwrap_or_default();
+
+			(
+				None,
+				format!("sha256:{name}"),
+				None,
+				source.read_json(config)?,
+			)
+		}
+		Source::Oci(_) => {
+			let (digest, manifest) = oci_manifest(source, platform)?;
+			let config = text(&manifest["config"]["digest"])
+				.ok_or_else(|| invalid("Image manifest has no config"))?;
+			let size = manifest["layers"]
+				.as_array()
+				.map(|x| x.iter().filter_map(|x| x["size"].as_u64()).sum());
+
+			(
+				digest,
+				config.clone(),
+				size,
+				source.read_json(&blob(&config))?,
+			)
+		}
+	};
+
+	Ok(runtime::Image {
+		digests: digest.iter().cloned().collect(),
+		digest,
+		id,
+		created: text(&config["created"]),
+		size,
+		os: text(&config["os"]),
+		architecture: text(&config["architecture"]),
+		variant: text(&config["variant"]),
+		labels: runtime::labels(&config["config"]["Labels"]),
+		entrypoint: runtime::strings(&config["config"]["Entrypoint"]),
+	})
+}
+
+fn blob(digest: &str) -> String {
+	format!("blobs/{}", digest.replacen(':', "/", 1))
+}
+
+/// Make an archive path absolute and resolve any `.` or `..`
+fn normalize(path: &Path) -> PathBuf {
+	runtime::normalize(&Path::new("/").join(path))
+}
+
+fn components(path: &Path) -> Vec<PathBuf> {
+	path.components()
+		.filter_map(|x| match x {
+			Component::Normal(x) => Some(PathBuf::from(x)),
+			_ => None,
+		})
+		.collect()
+}
+
+fn invalid(err: impl Display) -> DockerError {
+	DockerError::InvalidOutput(err.to_string())
+}
+
+/// A runtime which reads OCI layouts and `docker save` tarballs directly
+///
+/// No container is ever started, so only file based probes are supported.
+pub struct Archive {
+	source: Source,
+	platform: Option<String>,
+	image: RefCell<Option<Image>>,
+}
+
+impl Archive {
+	pub fn new(source: Source, platform: Option<String>) -> Self {
+		Self {
+			source,
+			platform,
+			image: RefCell::new(None),
+		}
+	}
+}
+
+impl ContainerRuntime for Archive {
+	fn pull(&self) -> Result<(), DockerError> {
+		match self.source.path().exists() {
+			true => Ok(()),
+			false => Err(DockerError::ImageNotFound(
+				self.source.path().display().to_string(),
+			)),
+		}
+	}
+
+	fn inspect(&self) -> Result<runtime::Image, DockerError> {
+		inspect(&self.source, self.platform.as_deref())
+	}
+
+	fn run(&self) -> Result<(), DockerError> {
+		*self.image.borrow_mut() = Some(Image::load(&self.source, self.platform.as_deref())?);
+		Ok(())
+	}
+
+	fn execute(
+		&self,
+		_args: &[String],
+		_timeout: Option<Duration>,
+	) -> Result<ExecOutput, DockerError> {
+		Err(DockerError::Unsupported(String::from(
+			"commands can't be run in an offline image",
+		)))
+	}
+
+	// Offline images are read from local files, so there's nothing to time out
+	fn read_file(&self, path: &str, _timeout: Option<Duration>) -> Result<Vec<u8>, DockerError> {
+		match self.image.borrow().as_ref() {
+			Some(x) => x.read(&self.source, path),
+			None => Err(DockerError::Unsupported(String::from(
+				"the image hasn't been loaded",
+			))),
+		}
+	}
+
+	fn read_dir(
+		&self,
+		path: &str,
+		_timeout: Option<Duration>,
+	) -> Result<Vec<Vec<u8>>, DockerError> {
+		match self.image.borrow().as_ref() {
+			Some(x) => x.read_dir(&self.source, path),
+			None => Err(DockerError::Unsupported(String::from(
+				"the image hasn't been loaded",
+			))),
+		}
+	}
+
+	fn stop(&self) -> Result<(), DockerError> {
+		self.image.borrow_mut().take();
+		Ok(())
+	}
+
+	fn clean(&self) -> Result<(), DockerError> {
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Archive, Source};
+	use crate::{docker::DockerError, runtime::ContainerRuntime};
+	use flate2::{write::GzEncoder, Compression};
+	use serde_json::json;
+	use std::{fs, io::Write, path::PathBuf};
+
+	enum Fixture<'a> {
+		File(&'a str, &'a [u8]),
+		Symlink(&'a str, &'a str),
+	}
+
+	fn tarball(entries: &[Fixture]) -> Vec<u8> {
+		let mut builder = tar::Builder::new(vec![]);
+
+		for entry in entries {
+			let mut header = tar::Header::new_gnu();
+			header.set_mode(0o644);
+
+			match entry {
+				Fixture::File(name, contents) => {
+					header.set_size(contents.len() as u64);
+					builder.append_data(&mut header, name, *contents).unwrap();
+				}
+				Fixture::Symlink(name, target) => {
+					header.set_entry_type(tar::EntryType::Symlink);
+					header.set_size(0);
+					builder.append_link(&mut header, name, target).unwrap();
+				}
+			}
+		}
+
+		builder.into_inner().unwrap()
+	}
+
+	fn gzip(data: &[u8]) -> Vec<u8> {
+		let mut encoder = GzEncoder::new(vec![], Compression::default());
+		encoder.write_all(data).unwrap();
+		encoder.finish().unwrap()
+	}
+
+	fn scratch(test: &str) -> PathBuf {
+		let path = std::env::temp_dir().join(format!("corrator-{test}-{}", std::process::id()));
+		drop(fs::remove_dir_all(&path));
+		fs::create_dir_all(&path).unwrap();
+		path
+	}
+
+	fn layers() -> (Vec<u8>, Vec<u8>) {
+		let lower = tarball(&[
+			Fixture::Symlink("etc/os-release", "../usr/lib/os-release"),
+			Fixture::File("usr/lib/os-release", b"VERSION_ID=1"),
+			Fixture::File("etc/removed", b"gone"),
+			Fixture::File("opt/app/old", b"gone"),
+		]);
+		let upper = gzip(&tarball(&[
+			Fixture::File("./usr/lib/os-release", b"VERSION_ID=2"),
+			Fixture::File("etc/.wh.removed", b""),
+			Fixture::File("opt/app/new", b"kept"),
+			Fixture::File("opt/app/.wh..wh..opq", b""),
+		]));
+
+		(lower, upper)
+	}
+
+	fn assert_merged(archive: &Archive) {
+		archive.pull().unwrap();
+		archive.run().unwrap();
+
+		assert_eq!(
+			archive.read_file("/etc/os-release", None).unwrap(),
+			b"VERSION_ID=2"
+		);
+		assert_eq!(archive.read_file("/opt/app/new", None).unwrap(), b"kept");
+		assert_eq!(archive.read_dir("/opt/app", None).unwrap(), [b"kept"]);
+		for path in ["/etc/removed", "/opt/app/old"] {
+			assert!(matches!(
+				archive.read_file(path, None),
+				Err(DockerError::FileNotFound(_))
+			));
+		}
+		assert!(matches!(
+			archive.execute(&[String::from("sh")], None),
+			Err(DockerError::Unsupported(_))
+		));
+	}
+
+	#[test]
+	fn reads_docker_archives() {
+		let (lower, upper) = layers();
+		let manifest = json!([{
+			"Config": "config.json",
+			"RepoTags": ["test:latest"],
+			"Layers": ["lower/layer.tar", "upper/layer.tar"],
+		}]);
+		let manifest = manifest.to_string();
+		let path = scratch("docker-archive").join("image.tar");
+		fs::write(
+			&path,
+			tarball(&[
+				Fixture::File("manifest.json", manifest.as_bytes()),
+				Fixture::File("config.json", br#"{"os":"linux","architecture":"amd64"}"#),
+				Fixture::File("lower/layer.tar", &lower),
+				Fixture::File("upper/layer.tar", &upper),
+			]),
+		)
+		.unwrap();
+
+		let source = Source::parse(&format!("docker-archive:{}", path.display())).unwrap();
+		assert_merged(&Archive::new(source.clone(), None));
+
+		let archive = Archive::new(source, Some(String::from("linux/arm64")));
+		assert!(matches!(archive.run(), Err(DockerError::ImageNotFound(_))));
+	}
+
+	#[test]
+	fn reads_oci_layouts() {
+		let (lower, upper) = layers();
+		let path = scratch("oci");
+		let blobs = path.join("blobs/sha256");
+		fs::create_dir_all(&blobs).unwrap();
+
+		let manifest = json!({
+			"config": { "digest": "sha256:config" },
+			"layers": [
+				{ "digest": "sha256:lower", "size": 10 },
+				{ "digest": "sha256:upper", "size": 20 },
+			],
+		});
+		let index = json!({
+			"manifests": [
+				{ "digest": "sha256:other", "platform": { "os": "linux", "architecture": "s390x" } },
+				{ "digest": "sha256:attestation", "platform": { "os": "unknown" } },
+				{ "digest": "sha256:manifest", "platform": { "os": "linux", "architecture": "arm64", "variant": "v8" } },
+			],
+		});
+		fs::write(blobs.join("lower"), lower).unwrap();
+		fs::write(blobs.join("upper"), upper).unwrap();
+		fs::write(
+			blobs.join("config"),
+			json!({
+				"os": "linux",
+				"architecture": "arm64",
+				"config": { "Labels": { "org.opencontainers.image.version": "1.0" } },
+			})
+			.to_string(),
+		)
+		.unwrap();
+		fs::write(blobs.join("manifest"), manifest.to_string()).unwrap();
+		fs::write(blobs.join("index"), index.to_string()).unwrap();
+		fs::write(
+			path.join("index.json"),
+			json!({ "manifests": [{ "digest": "sha256:index" }] }).to_string(),
+		)
+		.unwrap();
+
+		let source = Source::parse(&format!("oci:{}", path.display())).unwrap();
+		assert_merged(&Archive::new(
+			source.clone(),
+			Some(String::from("linux/arm64")),
+		));
+
+		let archive = Archive::new(source.clone(), Some(String::from("linux/arm64")));
+		let image = archive.inspect().unwrap();
+		assert_eq!(image.digest.as_deref(), Some("sha256:index"));
+		assert_eq!(image.id, "sha256:config");
+		assert_eq!(image.size, Some(30));
+		assert_eq!(image.platform().as_deref(), Some("linux/arm64"));
+		assert_eq!(image.labels["org.opencontainers.image.version"], "1.0");
+
+		let archive = Archive::new(source, Some(String::from("linux/riscv64")));
+		assert!(matches!(archive.run(), Err(DockerError::ImageNotFound(_))));
+	}
+
+	#[test]
+	fn missing_sources_are_not_found() {
+		let archive = Archive::new(Source::Oci(PathBuf::from("/nonexistent/corrator")), None);
+
+		assert!(matches!(archive.pull(), Err(DockerError::ImageNotFound(_))));
+	}
+}
@@ -1,4 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::application::{Check, Constraints};
+use crate::package::{Distro, Package};
+use crate::runtime::{Image, Probe, PullPolicy, Timeouts};
 
 /// Configuration details for a container
 ///
@@ -13,6 +18,107 @@ pub struct Container {
 
 	/// A list of tags for this container
 	pub tags: Option<Vec<String>>,
+
+	/// When to pull this container's image, overriding the global option
+	pub pull_policy: Option<PullPolicy>,
+
+	/// How to probe apps; "files" reads files without starting the container,
+	/// for images without a shell
+	#[serde(default)]
+	pub probe: Probe,
+
+	/// Platforms to query separately, e.g., `["linux/amd64", "linux/arm64"]`;
+	/// defaults to the host's platform
+	pub platforms: Option<Vec<String>>,
+
+	/// Limits on each step of querying this container, overriding the global ones
+	#[serde(default)]
+	pub timeouts: Timeouts,
+
+	/// The digest the image must resolve to, e.g., "sha256:..."
+	pub digest: Option<String>,
+
+	/// Environment variables for version commands, e.g., `JAVA_HOME`
+	#[serde(default)]
+	pub env: BTreeMap<String, String>,
+
+	/// The user to run version commands as
+	pub user: Option<String>,
+
+	/// The directory to run version commands in
+	pub workdir: Option<String>,
+
+	/// The network mode to run the container with; defaults to "none"
+	pub network: Option<String>,
+
+	/// Volumes to mount, e.g., `["/opt/certs:/certs:ro"]`
+	#[serde(default)]
+	pub volumes: Vec<String>,
+
+	/// Further arguments for `docker run`; not supported by the docker-api runtime
+	#[serde(default)]
+	pub run_args: Vec<String>,
+
+	/// Start the container with its image's entrypoint rather than a bare shell,
+	/// and run version commands through it, e.g., for conda or asdf images
+	#[serde(default)]
+	pub keep_entrypoint: bool,
+
+	/// Shell commands to run before each version command, e.g., `. ~/.nvm/nvm.sh`
+	pub init: Option<String>,
+
+	/// List every installed OS and language package, overriding the global option
+	pub inventory: Option<bool>,
+
+	/// Version constraints for apps in this container, by app name,
+	/// replacing those of the apps themselves
+	#[serde(default)]
+	pub constraints: BTreeMap<String, Constraints>,
+}
+
+impl Container {
+	/// Each platform this container should be queried on
+	///
+	/// `None` stands for the runtime's default platform.
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::container::Container;
+	/// let container = Container {
+	///     platforms: Some(vec![String::from("linux/arm64")]),
+	///     ..Default::default()
+	/// };
+	///
+	/// assert_eq!(container.platforms(), [Some(String::from("linux/arm64"))]);
+	/// assert_eq!(Container::default().platforms(), [None]);
+	/// ```
+	pub fn platforms(&self) -> Vec<Option<String>> {
+		match &self.platforms {
+			Some(x) if !x.is_empty() => x.iter().cloned().map(Some).collect(),
+			_ => vec![None],
+		}
+	}
+
+	/// The digest this container's image is pinned to, if any
+	///
+	/// Either given explicitly, or as part of the path, e.g., `ubuntu@sha256:...`
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::container::Container;
+	/// let container = Container {
+	///     path: String::from("ubuntu@sha256:67211c14fa74"),
+	///     ..Default::default()
+	/// };
+	///
+	/// assert_eq!(container.expected_digest(), Some("sha256:67211c14fa74"));
+	/// assert_eq!(Container::default().expected_digest(), None);
+	/// ```
+	pub fn expected_digest(&self) -> Option<&str> {
+		self.digest
+			.as_deref()
+			.or_else(|| self.path.split_once('@').map(|(_, x)| x))
+	}
 }
 
 // Added to simplify tests and documentation
@@ -22,6 +128,21 @@ impl Default for Container {
 			path: String::from("path"),
 			apps: vec![String::from("bash")],
 			tags: None,
+			pull_policy: None,
+			probe: Probe::default(),
+			platforms: None,
+			timeouts: Timeouts::default(),
+			digest: None,
+			env: BTreeMap::new(),
+			user: None,
+			workdir: None,
+			network: None,
+			volumes: vec![],
+			run_args: vec![],
+			keep_entrypoint: false,
+			init: None,
+			inventory: None,
+			constraints: BTreeMap::new(),
 		}
 	}
 }
@@ -32,10 +153,29 @@ impl Default for Container {
 /// for each defined application in a container.
 ///
 /// [`application::Status`]: ../application/Struct.status.html
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct Status {
 	pub name: String,
+
+	/// The platform queried, if one was requested
+	pub platform: Option<String>,
+
+	/// Details of the image queried, if it could be inspected
+	pub image: Option<Image>,
+
 	pub apps: Vec<crate::application::Status>,
+
+	/// Why the container couldn't be queried, if it failed
+	pub error: Option<String>,
+
+	/// How many attempts it took to pull the image
+	pub attempts: u32,
+
+	/// Every installed package, if an inventory was taken
+	pub inventory: Option<Vec<Package>>,
+
+	/// The distribution the image is built from, if an inventory found one
+	pub distro: Option<Distro>,
 }
 
 impl Status {
@@ -46,7 +186,16 @@ impl Status {
 	/// corrator::container::Status::new(String::from("corrator"));
 	/// ```
 	pub fn new(name: String) -> Self {
-		Self { name, apps: vec![] }
+		Self {
+			name,
+			platform: None,
+			image: None,
+			apps: vec![],
+			error: None,
+			attempts: 0,
+			inventory: None,
+			distro: None,
+		}
 	}
 
 	/// Serialize a Status instance as JSON data
@@ -71,24 +220,101 @@ impl From<Status> for String {
 	/// # Example
 	///
 	/// ---Container: ubuntu-----------------------------
+	///     digest: sha256:67211c14fa74...
+	///     image: sha256:97271d29cb79... (linux/amd64, 2023-10-04T13:08:57Z, 77.8 MB)
+	///     label: org.opencontainers.image.version=22.04
 	///     bash           5.1.16     
 	///     grep           3.7        
 	///     ubuntu         22.04      
-
+	///     openssl        1.1.1w     
+	///                    failed check: 1.1.1w is below minimum 3.0
+	///     zsh            unknown    app is not defined
+	///     inventory: 2 packages
+	///       dpkg  bash                           5.1-6ubuntu1
+	///       pip   requests                       2.31.0
 	fn from(value: Status) -> Self {
 		let mut output = vec![];
-		output.push(format!("---Container: {:-<35}", value.name));
+		let title = match &value.platform {
+			Some(x) => format!("{} ({x})", value.name),
+			None => value.name.clone(),
+		};
+		output.push(format!("---Container: {title:-<35}"));
+
+		if let Some(x) = &value.image {
+			if let Some(digest) = &x.digest {
+				output.push(format!("\tdigest: {digest}"));
+			}
+
+			let mut details = vec![];
+			details.extend(x.platform());
+			details.extend(x.created.clone());
+			details.extend(x.size.map(|x| format!("{:.1} MB", x as f64 / 1_000_000.0)));
+			match details.is_empty() {
+				true => output.push(format!("\timage: {}", x.id)),
+				false => output.push(format!("\timage: {} ({})", x.id, details.join(", "))),
+			}
+
+			for (label, value) in &x.labels {
+				output.push(format!("\tlabel: {label}={value}"));
+			}
+		}
+
+		match (&value.error, value.attempts) {
+			(Some(x), attempts) if attempts > 1 => {
+				output.push(format!("\terror: {x} (after {attempts} attempts)"))
+			}
+			(Some(x), _) => output.push(format!("\terror: {x}")),
+			(None, _) => (),
+		}
 
 		for app in value.apps.iter() {
-			let eol_status: String = match &app.eol_status {
-				Some(x) => x.to_string(),
-				None => String::from(""),
+			let eol_status: String = match (&app.eol_status, &app.failure) {
+				(_, Some(x)) => x.to_string(),
+				(Some(x), None) => x.to_string(),
+				(None, None) => String::from(""),
 			};
+			let version = app.version.as_deref().unwrap_or("unknown");
 
 			output.push(format!(
 				"\t{: <15}{: <10} {}",
-				&app.name, &app.version, eol_status,
+				&app.name, version, eol_status,
 			));
+
+			if let Some(x) = &app.failure {
+				let exit_code = x.exit_code.map(|x| x.to_string());
+				let attempts = Some(app.attempts).filter(|x| *x > 1).map(|x| x.to_string());
+				let details = [
+					("command", &x.command),
+					("file", &x.file),
+					("package", &x.package),
+					("regex", &x.regex),
+					("exit code", &exit_code),
+					("output", &x.output),
+					("message", &x.message),
+					("attempts", &attempts),
+				];
+
+				for (label, detail) in details {
+					if let Some(detail) = detail {
+						output.push(format!("\t{: <15}{label}: {}", "", detail.trim()));
+					}
+				}
+			}
+
+			if let Some(Check::Fail { reason }) = &app.check {
+				output.push(format!("\t{: <15}failed check: {reason}", ""));
+			}
+		}
+
+		if let Some(packages) = &value.inventory {
+			output.push(format!("\tinventory: {} packages", packages.len()));
+
+			for x in packages {
+				output.push(format!(
+					"\t  {: <6}{: <30} {}",
+					x.manager, x.name, x.version
+				));
+			}
 		}
 
 		output.join("\n")
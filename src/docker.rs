@@ -1,73 +1,782 @@
-use std::error::Error;
-use std::process::{self, Command};
+use serde_json::Value;
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::{error::Error, fmt, io, string::FromUtf8Error};
+
+use crate::runtime::{self, ContainerRuntime, ExecOutput, Image, Probe, PullPolicy, Settings};
+
+pub mod api;
+mod auth;
+
+/// Failures from driving a container runtime
+#[derive(Debug)]
+pub enum DockerError {
+	/// The runtime could not be reached, e.g., the binary isn't installed
+	Spawn(io::Error),
+	/// A runtime command exited unsuccessfully
+	NonZeroExit { status: Option<i32>, stderr: String },
+	/// The requested image does not exist locally or in its registry
+	ImageNotFound(String),
+	/// The image exists but could not be pulled
+	PullFailed { image: String, message: String },
+	/// The runtime returned output that couldn't be read
+	InvalidOutput(String),
+	/// A file requested from a container doesn't exist
+	FileNotFound(String),
+	/// The runtime can't perform the requested operation
+	Unsupported(String),
+	/// The Docker Engine API responded with an error
+	Api(api::ApiError),
+	/// A step took longer than its configured timeout
+	Timeout(Duration),
+	/// A pinned image resolved to a different digest than expected
+	DigestMismatch {
+		expected: String,
+		actual: Option<String>,
+	},
+}
+
+impl Error for DockerError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			Self::Spawn(x) => Some(x),
+			Self::Api(x) => Some(x),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for DockerError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Spawn(x) => write!(f, "Unable to reach container runtime: {x}"),
+			Self::NonZeroExit {
+				status: Some(x),
+				stderr,
+			} => write!(f, "Runtime command exited with {x}: {stderr}"),
+			Self::NonZeroExit {
+				status: None,
+				stderr,
+			} => write!(f, "Runtime command was terminated: {stderr}"),
+			Self::ImageNotFound(x) => write!(f, "Image not found: {x}"),
+			Self::PullFailed { image, message } => {
+				write!(f, "Unable to pull image {image}: {message}")
+			}
+			Self::InvalidOutput(x) => write!(f, "Unable to read runtime output: {x}"),
+			Self::FileNotFound(x) => write!(f, "File not found: {x}"),
+			Self::Unsupported(x) => write!(f, "Not supported: {x}"),
+			Self::Api(x) => x.fmt(f),
+			Self::Timeout(x) => write!(f, "Timed out after {}s", x.as_secs_f32()),
+			Self::DigestMismatch {
+				expected,
+				actual: Some(actual),
+			} => write!(
+				f,
+				"Image digest {actual} does not match expected {expected}"
+			),
+			Self::DigestMismatch {
+				expected,
+				actual: None,
+			} => write!(f, "Image has no digest to match expected {expected}"),
+		}
+	}
+}
+
+impl From<io::Error> for DockerError {
+	fn from(value: io::Error) -> Self {
+		Self::Spawn(value)
+	}
+}
+
+impl From<FromUtf8Error> for DockerError {
+	fn from(value: FromUtf8Error) -> Self {
+		Self::InvalidOutput(value.to_string())
+	}
+}
+
+impl From<api::ApiError> for DockerError {
+	fn from(value: api::ApiError) -> Self {
+		Self::Api(value)
+	}
+}
+
+impl DockerError {
+	/// Whether the failure is likely to go away if retried
+	///
+	/// Network errors, registry 5xx responses and a busy daemon are
+	/// transient, while a missing image or a step which timed out are not.
+	pub fn is_transient(&self) -> bool {
+		match self {
+			Self::Spawn(x) => matches!(
+				x.kind(),
+				io::ErrorKind::ConnectionReset
+					| io::ErrorKind::ConnectionAborted
+					| io::ErrorKind::BrokenPipe
+					| io::ErrorKind::UnexpectedEof
+					| io::ErrorKind::Interrupted
+			),
+			Self::NonZeroExit { stderr: x, .. } | Self::PullFailed { message: x, .. } => {
+				is_transient(x)
+			}
+			Self::Api(x) => {
+				(x.status >= 500 && !is_not_found(&x.message)) || is_transient(&x.message)
+			}
+			_ => false,
+		}
+	}
+
+	/// Classify the output of a failed `pull` command
+	fn from_pull(image: &str, output: &Output) -> Self {
+		let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+
+		if is_not_found(&stderr) {
+			Self::ImageNotFound(String::from(image))
+		} else if stderr.contains("pull") || stderr.contains("registry") {
+			Self::PullFailed {
+				image: String::from(image),
+				message: stderr,
+			}
+		} else {
+			Self::from(output)
+		}
+	}
+
+	/// Classify the output of a failed `cp` command out of a container
+	fn from_copy(path: &str, output: &Output) -> Self {
+		let stderr = String::from_utf8_lossy(&output.stderr).to_lowercase();
+		let missing = [
+			"could not find the file",
+			"no such container:path",
+			"could not be found on container",
+		];
+
+		match missing.iter().any(|x| stderr.contains(x)) {
+			true => Self::FileNotFound(String::from(path)),
+			false => Self::from(output),
+		}
+	}
+
+	/// Classify the output of a failed `run` command, which never pulls
+	fn from_run(image: &str, output: &Output) -> Self {
+		match is_not_found(&String::from_utf8_lossy(&output.stderr)) {
+			true => Self::ImageNotFound(String::from(image)),
+			false => Self::from(output),
+		}
+	}
+}
+
+impl From<&Output> for DockerError {
+	fn from(value: &Output) -> Self {
+		Self::NonZeroExit {
+			status: value.status.code(),
+			stderr: String::from_utf8_lossy(&value.stderr).trim().to_string(),
+		}
+	}
+}
+
+/// Run a command to completion, killing it if it outlasts `timeout`
+pub(crate) fn output(
+	command: &mut Command,
+	timeout: Option<Duration>,
+) -> Result<Output, DockerError> {
+	let timeout = match timeout {
+		Some(x) => x,
+		None => return Ok(command.output()?),
+	};
+
+	let mut child = command
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()?;
+	// Pipes are drained while waiting, so a chatty command can't fill one and stall
+	let stdout = drain(child.stdout.take());
+	let stderr = drain(child.stderr.take());
+	let deadline = Instant::now() + timeout;
+
+	let status = loop {
+		if let Some(x) = child.try_wait()? {
+			break x;
+		}
+
+		if Instant::now() >= deadline {
+			// The command may have exited since it was last checked
+			let _ = child.kill();
+			child.wait()?;
+			return Err(DockerError::Timeout(timeout));
+		}
+
+		thread::sleep(Duration::from_millis(10));
+	};
+
+	Ok(Output {
+		status,
+		stdout: stdout.join().unwrap_or_default(),
+		stderr: stderr.join().unwrap_or_default(),
+	})
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+	thread::spawn(move || {
+		let mut contents = vec![];
+		if let Some(mut x) = pipe {
+			let _ = x.read_to_end(&mut contents);
+		}
+		contents
+	})
+}
+
+/// Whether a runtime error message describes a temporary problem
+fn is_transient(message: &str) -> bool {
+	let message = message.to_lowercase();
+	let transient = [
+		"tls handshake timeout",
+		"i/o timeout",
+		"connection reset",
+		"connection refused",
+		"unexpected eof",
+		"too many requests",
+		"500 internal server error",
+		"502 bad gateway",
+		"503 service unavailable",
+		"504 gateway timeout",
+		"temporary failure",
+		"try again",
+		"daemon is busy",
+	];
+
+	!is_not_found(&message) && transient.iter().any(|x| message.contains(x))
+}
+
+/// Whether `exec` failed in the runtime itself, rather than in the command
+///
+/// Both docker and podman exit with 125 and prefix their own errors.
+fn is_exec_failure(output: &Output) -> bool {
+	let stderr = String::from_utf8_lossy(&output.stderr);
+
+	output.status.code() == Some(125)
+		&& (stderr.starts_with("Error response from daemon:") || stderr.starts_with("Error: "))
+}
+
+/// Whether a runtime error message means the image doesn't exist
+///
+/// Only registry and daemon phrases are matched, since other things can
+/// be "not found" too, e.g., a shell missing from a distroless image.
+pub(crate) fn is_not_found(message: &str) -> bool {
+	let message = message.to_lowercase();
+	let not_found = [
+		"manifest unknown",
+		"pull access denied",
+		"repository does not exist",
+		"no such image",
+		"image not known",
+	];
+
+	not_found.iter().any(|x| message.contains(x))
+}
 
 pub struct Docker<'a> {
 	name: &'a str,
-	path: &'a str,
+	path: String,
+	settings: Settings,
+	program: &'static str,
+	global_args: Vec<String>,
 }
 
 impl Docker<'_> {
-	pub fn new<'a>(name: &'a str, path: &'a str) -> Docker<'a> {
-		Docker { name, path }
+	pub fn new<'a>(name: &'a str, path: &str, settings: Settings) -> Docker<'a> {
+		Docker::with_program(name, path, settings, "docker", vec![])
+	}
+
+	/// Create an instance driving a docker compatible CLI other than `docker`
+	pub(crate) fn with_program<'a>(
+		name: &'a str,
+		path: &str,
+		settings: Settings,
+		program: &'static str,
+		global_args: Vec<String>,
+	) -> Docker<'a> {
+		Docker {
+			name,
+			path: String::from(path),
+			settings,
+			program,
+			global_args,
+		}
+	}
+
+	pub(crate) fn path(&self) -> &str {
+		&self.path
+	}
+
+	/// The same runtime for another image
+	pub(crate) fn with_path(&self, path: &str) -> Self {
+		Self {
+			path: String::from(path),
+			settings: self.settings.clone(),
+			global_args: self.global_args.clone(),
+			..*self
+		}
+	}
+
+	pub(crate) fn command(&self) -> Command {
+		let mut command = Command::new(self.program);
+		command.args(&self.global_args);
+		command
+	}
+
+	fn platform_args(&self) -> Vec<&str> {
+		match &self.settings.platform {
+			Some(x) => vec!["--platform", x],
+			None => vec![],
+		}
+	}
+
+	/// Remove containers created by corrator, e.g., left behind by a crash
+	///
+	/// Only containers from the given run are removed, if one is given.
+	/// Otherwise, those whose corrator process may still be running are kept.
+	/// Returns the names of the removed containers.
+	pub fn reap(&self, run_id: Option<&str>) -> Result<Vec<String>, DockerError> {
+		let filter = match run_id {
+			Some(x) => format!("label={}={x}", runtime::LABEL),
+			None => format!("label={}", runtime::LABEL),
+		};
+		let output = self
+			.command()
+			.args(["ps", "--all", "--format", "{{.Names}}", "--filter", &filter])
+			.output()?;
+
+		if !output.status.success() {
+			return Err(DockerError::from(&output));
+		}
+
+		let mut names: Vec<String> = String::from_utf8(output.stdout)?
+			.lines()
+			.map(String::from)
+			.collect();
+
+		if run_id.is_none() && !names.is_empty() {
+			names = self.abandoned(names)?;
+		}
+
+		if !names.is_empty() {
+			let output = self.command().args(["rm", "-f"]).args(&names).output()?;
+
+			if !output.status.success() {
+				return Err(DockerError::from(&output));
+			}
+		}
+
+		Ok(names)
 	}
 
-	pub fn run(&self) -> Result<(), Box<dyn Error>> {
-		Command::new("docker")
-			.arg("run")
-			.arg("--rm")
-			.args(["--entrypoint", ""])
-			.args(["--pull", "always"])
+	/// Those of the named containers whose corrator process has exited
+	fn abandoned(&self, names: Vec<String>) -> Result<Vec<String>, DockerError> {
+		let output = self
+			.command()
+			.args(["container", "inspect"])
+			.args(&names)
+			.output()?;
+
+		if !output.status.success() {
+			return Err(DockerError::from(&output));
+		}
+
+		let inspect: Value = serde_json::from_slice(&output.stdout)
+			.map_err(|x| DockerError::InvalidOutput(x.to_string()))?;
+		let in_use: Vec<&str> = inspect
+			.as_array()
+			.into_iter()
+			.flatten()
+			.filter(|x| runtime::is_in_use(&x["Config"]["Labels"]))
+			// Docker prefixes names with a slash, podman doesn't
+			.filter_map(|x| Some(x["Name"].as_str()?.trim_start_matches('/')))
+			.collect();
+
+		Ok(names
+			.into_iter()
+			.filter(|x| !in_use.contains(&x.as_str()))
+			.collect())
+	}
+
+	/// Environment, user and working directory arguments, for `run` and `exec`
+	fn process_args(&self) -> Vec<String> {
+		let mut args = vec![];
+
+		for pair in self.settings.env_pairs() {
+			args.extend([String::from("--env"), pair]);
+		}
+		if let Some(x) = &self.settings.user {
+			args.extend([String::from("--user"), x.clone()]);
+		}
+		if let Some(x) = &self.settings.workdir {
+			args.extend([String::from("--workdir"), x.clone()]);
+		}
+
+		args
+	}
+
+	/// Whether the image is present for the requested platform; a tag pulled
+	/// for another platform doesn't count
+	fn image_exists(&self) -> Result<bool, DockerError> {
+		let output = self
+			.command()
+			.args(["image", "inspect", "--format", "{{json .}}", &self.path])
+			.output()?;
+
+		if !output.status.success() {
+			return Ok(false);
+		}
+
+		let inspect = serde_json::from_slice(&output.stdout)
+			.map_err(|x| DockerError::InvalidOutput(x.to_string()))?;
+		let image = Image::from_inspect(&inspect, &self.path);
+
+		Ok(self
+			.settings
+			.platform
+			.as_deref()
+			.is_none_or(|x| image.matches_platform(x)))
+	}
+}
+
+impl ContainerRuntime for Docker<'_> {
+	fn pull(&self) -> Result<(), DockerError> {
+		match self.settings.pull_policy {
+			PullPolicy::Always => (),
+			PullPolicy::IfMissing if self.image_exists()? => return Ok(()),
+			PullPolicy::IfMissing => (),
+			PullPolicy::Never if self.image_exists()? => return Ok(()),
+			PullPolicy::Never => return Err(DockerError::ImageNotFound(self.path.clone())),
+		}
+
+		let output = output(
+			self.command()
+				.arg("pull")
+				.args(self.platform_args())
+				.arg(&self.path),
+			self.settings.timeouts.pull(),
+		)?;
+
+		match output.status.success() {
+			true => Ok(()),
+			false => Err(DockerError::from_pull(&self.path, &output)),
+		}
+	}
+
+	fn inspect(&self) -> Result<Image, DockerError> {
+		// By the container's image ID, since the tag may since have been
+		// pulled for another platform
+		let output = self
+			.command()
+			.args([
+				"container",
+				"inspect",
+				"--format",
+				"{{.Image}}",
+				(self.name),
+			])
+			.output()?;
+		if !output.status.success() {
+			return Err(DockerError::from(&output));
+		}
+
+		let id = String::from_utf8(output.stdout)?;
+		let output = self
+			.command()
+			.args(["image", "inspect", "--format", "{{json .}}", id.trim()])
+			.output()?;
+
+		match output.status.success() {
+			true => {
+				let inspect = serde_json::from_slice(&output.stdout)
+					.map_err(|x| DockerError::InvalidOutput(x.to_string()))?;
+				Ok(Image::from_inspect(&inspect, &self.path))
+			}
+			false => Err(DockerError::from(&output)),
+		}
+	}
+
+	fn run(&self) -> Result<(), DockerError> {
+		let mut command = self.command();
+		match self.settings.probe {
+			Probe::Exec => command.args(["run", "-dit"]),
+			Probe::Files => command.arg("create"),
+		};
+
+		if !self.settings.keep_entrypoint {
+			command.args(["--entrypoint", ""]);
+		}
+
+		command.args(["--pull", "never"]);
+		for (name, value) in runtime::container_labels() {
+			command.args(["--label", &format!("{name}={value}")]);
+		}
+		command
+			.args(self.platform_args())
 			.args(["--name", (self.name)])
-			.arg("-dit")
-			.arg(self.path)
-			.arg("sh")
-			.output()
-			.unwrap_or_else(|err| {
-				eprintln!("Unable to stand docker container up: {err}");
-				process::exit(1);
-			});
+			.args(["--network", self.settings.network()])
+			.args(self.process_args());
+		for volume in &self.settings.volumes {
+			command.args(["--volume", volume]);
+		}
+		command
+			.args(&self.settings.run_args)
+			.arg(&self.path)
+			.arg("sh");
+		let output = output(&mut command, self.settings.timeouts.start())?;
+
+		match output.status.success() {
+			true => Ok(()),
+			false => Err(DockerError::from_run(&self.path, &output)),
+		}
+	}
+
+	fn read_file(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<u8>, DockerError> {
+		runtime::follow_links(path, |path| {
+			let output = output(
+				self.command()
+					.args(["cp", &format!("{}:{path}", self.name), "-"]),
+				timeout,
+			)?;
 
-		Ok(())
+			match output.status.success() {
+				true => runtime::unarchive_file(&output.stdout),
+				false => Err(DockerError::from_copy(path, &output)),
+			}
+		})
 	}
 
-	pub fn stop(&self, remove: bool) -> Result<(), Box<dyn Error>> {
-		Command::new("docker")
-			.args(["stop", (self.name)])
-			.output()
-			.unwrap_or_else(|err| {
-				eprintln!("Unable to stop docker container: {err}");
-				process::exit(1);
-			});
+	fn read_dir(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<Vec<u8>>, DockerError> {
+		let output = output(
+			self.command()
+				.args(["cp", &format!("{}:{path}", self.name), "-"]),
+			timeout,
+		)?;
 
-		if remove {
-			self.clean().expect("Unable to clean up image");
+		match output.status.success() {
+			true => runtime::unarchive_dir(&output.stdout),
+			false => Err(DockerError::from_copy(path, &output)),
 		}
+	}
+
+	fn stop(&self) -> Result<(), DockerError> {
+		let output = self.command().args(["rm", "-f", (self.name)]).output()?;
 
-		Ok(())
+		match output.status.success() {
+			true => Ok(()),
+			false => Err(DockerError::from(&output)),
+		}
 	}
 
-	pub fn execute(&self, args: &str) -> String {
-		let output = Command::new("docker")
-			.args(["exec", (self.name)])
-			.args(args.split(' '))
-			.output()
-			.unwrap_or_else(|err| {
-				eprintln!("Unable to run docker command: {err}");
-				process::exit(1);
-			});
+	fn execute(
+		&self,
+		args: &[String],
+		timeout: Option<Duration>,
+	) -> Result<ExecOutput, DockerError> {
+		let output = output(
+			self.command()
+				.arg("exec")
+				.args(self.process_args())
+				.arg(self.name)
+				.args(args),
+			timeout,
+		)?;
 
-		String::from_utf8(output.stdout).unwrap()
+		// The runtime failed, rather than the command; e.g., the daemon is busy
+		if is_exec_failure(&output) {
+			return Err(DockerError::from(&output));
+		}
+
+		Ok(ExecOutput {
+			stdout: String::from_utf8(output.stdout)?,
+			stderr: String::from_utf8(output.stderr)?,
+			status: output.status.code(),
+		})
 	}
 
-	pub fn clean(&self) -> Result<(), Box<dyn Error>> {
-		Command::new("docker")
-			.args(["rmi", "-f", (self.path)])
-			.output()
-			.unwrap_or_else(|err| {
-				eprintln!("Unable to remove docker image: {err}");
-				process::exit(1);
-			});
+	fn clean(&self) -> Result<(), DockerError> {
+		let output = self.command().args(["rmi", "-f", &self.path]).output()?;
+
+		match output.status.success() {
+			true => Ok(()),
+			false => Err(DockerError::from(&output)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{api::ApiError, output, Docker, DockerError};
+	use crate::runtime::{ContainerRuntime, PullPolicy, Settings};
+	use std::process::Command;
+	use std::time::Duration;
+
+	#[test]
+	fn passes_process_settings() {
+		let settings = Settings {
+			env: [(String::from("PATH"), String::from("/opt/bin:/usr/bin"))].into(),
+			workdir: Some(String::from("/srv")),
+			..Default::default()
+		};
+		let docker = Docker::new("test", "ubuntu", settings);
+
+		assert_eq!(
+			docker.process_args(),
+			["--env", "PATH=/opt/bin:/usr/bin", "--workdir", "/srv"]
+		);
+	}
+
+	#[test]
+	fn classifies_transient_errors() {
+		let pull = |message: &str| DockerError::PullFailed {
+			image: String::from("ubuntu"),
+			message: String::from(message),
+		};
+		let api = |status, message: &str| {
+			DockerError::Api(ApiError {
+				status,
+				message: String::from(message),
+			})
+		};
+
+		assert!(pull("net/http: TLS handshake timeout").is_transient());
+		assert!(pull("received unexpected HTTP status: 503 Service Unavailable").is_transient());
+		assert!(api(500, "server error").is_transient());
+		assert!(!pull("manifest unknown").is_transient());
+		assert!(!api(500, "manifest unknown: manifest unknown").is_transient());
+		assert!(!api(409, "conflict").is_transient());
+		assert!(!DockerError::ImageNotFound(String::from("ubuntu")).is_transient());
+		assert!(!DockerError::Timeout(Duration::from_secs(1)).is_transient());
+	}
+
+	#[test]
+	fn runtime_exec_failures_are_errors() {
+		// `sh -c` stands in for the runtime, ignoring the exec arguments after it
+		let exec = |script: &str| {
+			let settings = Settings::default();
+			let args = vec![String::from("-c"), String::from(script), String::from("sh")];
+			Docker::with_program("name", "path", settings, "sh", args)
+				.execute(&[String::from("true")], None)
+		};
+
+		let err = exec("echo 'Error response from daemon: i/o timeout' >&2; exit 125").unwrap_err();
+		assert!(err.is_transient());
+
+		let output = exec("echo 'test: 1.2.3' >&2; exit 125").unwrap();
+		assert_eq!(output.status, Some(125));
+	}
+
+	#[test]
+	fn reaps_containers_of_exited_runs() {
+		// `sh -c` stands in for the runtime, answering by subcommand
+		let inspect = format!(
+			r#"[{{"Name":"/old","Config":{{"Labels":{{"rs.corrator.pid":"4294967295"}}}}}},
+			{{"Name":"/live","Config":{{"Labels":{{"rs.corrator.pid":"{}"}}}}}}]"#,
+			std::process::id()
+		);
+		let script = format!(
+			r#"case "$1" in
+				ps) printf 'old\nlive\n' ;;
+				container) printf '%s' '{inspect}' ;;
+				rm) [ "$*" = "rm -f old" ] ;;
+			esac"#
+		);
+		let args = vec![String::from("-c"), script, String::from("sh")];
+		let docker = Docker::with_program("", "", Settings::default(), "sh", args);
+
+		assert_eq!(docker.reap(None).unwrap(), ["old"]);
+	}
+
+	#[test]
+	fn images_for_another_platform_are_missing() {
+		// `sh -c` stands in for the runtime, with the tag present for amd64
+		let pull = |platform: &str| {
+			let settings = Settings {
+				pull_policy: PullPolicy::Never,
+				platform: Some(String::from(platform)),
+				..Default::default()
+			};
+			let script = r#"printf '{"Id":"sha256:amd","Os":"linux","Architecture":"amd64"}'"#;
+			let args = vec![String::from("-c"), String::from(script), String::from("sh")];
+			Docker::with_program("", "ubuntu", settings, "sh", args).pull()
+		};
+
+		assert!(pull("linux/amd64").is_ok());
+		assert!(matches!(
+			pull("linux/arm64"),
+			Err(DockerError::ImageNotFound(_))
+		));
+	}
+
+	#[test]
+	fn classifies_run_failures() {
+		let failed = |stderr: &str| {
+			let output = Command::new("sh")
+				.args(["-c", &format!("echo '{stderr}' >&2; exit 127")])
+				.output()
+				.unwrap();
+			DockerError::from_run("ubuntu", &output)
+		};
+
+		assert!(matches!(
+			failed("Error response from daemon: No such image: ubuntu:latest"),
+			DockerError::ImageNotFound(_)
+		));
+		assert!(matches!(
+			failed("exec: \"sh\": executable file not found in $PATH: unknown"),
+			DockerError::NonZeroExit {
+				status: Some(127),
+				..
+			}
+		));
+	}
+
+	#[test]
+	fn classifies_copy_failures() {
+		let failed = |stderr: &str| {
+			let output = Command::new("sh")
+				.args(["-c", &format!("echo '{stderr}' >&2; exit 1")])
+				.output()
+				.unwrap();
+			DockerError::from_copy("/etc/os-release", &output)
+		};
+
+		for stderr in [
+			"Error response from daemon: Could not find the file /etc/os-release in container x",
+			"Error: No such container:path: x:/etc/os-release",
+			"Error: \"/etc/os-release\" could not be found on container x: no such file or directory",
+		] {
+			assert!(matches!(failed(stderr), DockerError::FileNotFound(_)));
+		}
+		assert!(matches!(
+			failed("Error response from daemon: No such container: x"),
+			DockerError::NonZeroExit { .. }
+		));
+	}
+
+	#[test]
+	fn output_within_timeout() {
+		let result = output(
+			Command::new("sh").args(["-c", "echo out; echo err >&2"]),
+			Some(Duration::from_secs(10)),
+		)
+		.unwrap();
+
+		assert!(result.status.success());
+		assert_eq!(result.stdout, b"out\n");
+		assert_eq!(result.stderr, b"err\n");
+	}
+
+	#[test]
+	fn output_killed_after_timeout() {
+		let timeout = Duration::from_millis(100);
+
+		let err = output(Command::new("sleep").arg("10"), Some(timeout)).unwrap_err();
 
-		Ok(())
+		assert!(matches!(err, DockerError::Timeout(x) if x == timeout));
 	}
 }
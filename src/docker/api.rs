@@ -0,0 +1,1179 @@
+use serde_json::{json, Value};
+use std::{
+	collections::BTreeMap,
+	error::Error,
+	fmt,
+	io::{self, Read, Write},
+	net::TcpStream,
+	os::unix::net::UnixStream,
+	path::PathBuf,
+	time::{Duration, Instant},
+};
+
+use crate::docker::{auth, is_not_found, DockerError};
+use crate::runtime::{self, ContainerRuntime, ExecOutput, Image, Probe, PullPolicy, Settings};
+
+const API_VERSION: &str = "v1.41";
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/// An error response returned by the Docker Engine API
+#[derive(Debug)]
+pub struct ApiError {
+	pub status: u16,
+	pub message: String,
+}
+impl Error for ApiError {}
+impl fmt::Display for ApiError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Docker API error ({}): {}", self.status, self.message)
+	}
+}
+
+/// Where the Docker Engine API is listening
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+	Unix(PathBuf),
+	Tcp(String),
+}
+
+impl Endpoint {
+	/// Resolve the endpoint from `DOCKER_HOST`, falling back to the default socket
+	pub fn from_env() -> Self {
+		match std::env::var("DOCKER_HOST") {
+			Ok(x) if !x.is_empty() => Self::parse(&x),
+			_ => Self::Unix(PathBuf::from(DEFAULT_SOCKET)),
+		}
+	}
+
+	/// Parse a `DOCKER_HOST` style address
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::docker::api::Endpoint;
+	/// # use std::path::PathBuf;
+	/// assert_eq!(
+	///     Endpoint::parse("unix:///run/user/1000/docker.sock"),
+	///     Endpoint::Unix(PathBuf::from("/run/user/1000/docker.sock"))
+	/// );
+	/// assert_eq!(
+	///     Endpoint::parse("tcp://127.0.0.1:2375"),
+	///     Endpoint::Tcp(String::from("127.0.0.1:2375"))
+	/// );
+	/// ```
+	pub fn parse(host: &str) -> Self {
+		match host.split_once("://") {
+			Some(("tcp", x)) | Some(("http", x)) => {
+				Self::Tcp(String::from(x.trim_end_matches('/')))
+			}
+			Some((_, x)) => Self::Unix(PathBuf::from(x)),
+			None => Self::Unix(PathBuf::from(host)),
+		}
+	}
+
+	fn request(
+		&self,
+		method: &str,
+		path: &str,
+		body: Option<&Value>,
+	) -> Result<Response, DockerError> {
+		self.request_within(method, path, body, None)
+	}
+
+	/// Make a request, giving up if the whole response takes longer than `timeout`
+	fn request_within(
+		&self,
+		method: &str,
+		path: &str,
+		body: Option<&Value>,
+		timeout: Option<Duration>,
+	) -> Result<Response, DockerError> {
+		self.send(method, path, &[], body, timeout)
+	}
+
+	/// Make a request with extra headers, e.g., `X-Registry-Auth`
+	fn send(
+		&self,
+		method: &str,
+		path: &str,
+		headers: &[(&str, &str)],
+		body: Option<&Value>,
+		timeout: Option<Duration>,
+	) -> Result<Response, DockerError> {
+		let body = match body {
+			Some(x) => x.to_string().into_bytes(),
+			None => vec![],
+		};
+
+		let mut request = format!(
+			"{method} /{API_VERSION}{path} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n"
+		)
+		.into_bytes();
+		for (name, value) in headers {
+			request.extend_from_slice(format!("{name}: {value}\r\n").as_bytes());
+		}
+		if !body.is_empty() {
+			request.extend_from_slice(b"Content-Type: application/json\r\n");
+		}
+		request.extend_from_slice(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes());
+		request.extend_from_slice(&body);
+
+		let raw = match self {
+			Self::Unix(x) => exchange(UnixStream::connect(x)?, &request, timeout, |x, y| {
+				x.set_read_timeout(y)
+			})?,
+			Self::Tcp(x) => exchange(TcpStream::connect(x)?, &request, timeout, |x, y| {
+				x.set_read_timeout(y)
+			})?,
+		};
+
+		Response::parse(&raw)
+	}
+}
+
+/// Send a request and read the whole response, within an overall deadline
+fn exchange<S: Read + Write>(
+	mut stream: S,
+	request: &[u8],
+	timeout: Option<Duration>,
+	set_read_timeout: impl Fn(&S, Option<Duration>) -> io::Result<()>,
+) -> Result<Vec<u8>, DockerError> {
+	stream.write_all(request)?;
+
+	let timeout = match timeout {
+		Some(x) => x,
+		None => {
+			let mut raw = vec![];
+			stream.read_to_end(&mut raw)?;
+			return Ok(raw);
+		}
+	};
+
+	// Socket timeouts apply to each read, so shrink them as the deadline nears
+	let deadline = Instant::now() + timeout;
+	let mut raw = vec![];
+	let mut buffer = [0; 8192];
+	loop {
+		let remaining = deadline.saturating_duration_since(Instant::now());
+		if remaining.is_zero() {
+			return Err(DockerError::Timeout(timeout));
+		}
+		set_read_timeout(&stream, Some(remaining))?;
+
+		match stream.read(&mut buffer) {
+			Ok(0) => return Ok(raw),
+			Ok(n) => raw.extend_from_slice(&buffer[..n]),
+			Err(x) if x.kind() == io::ErrorKind::Interrupted => (),
+			Err(x)
+				if matches!(
+					x.kind(),
+					io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+				) =>
+			{
+				return Err(DockerError::Timeout(timeout))
+			}
+			Err(x) => return Err(x.into()),
+		}
+	}
+}
+
+struct Response {
+	status: u16,
+	body: Vec<u8>,
+}
+
+impl Response {
+	fn parse(raw: &[u8]) -> Result<Self, DockerError> {
+		let split = raw
+			.windows(4)
+			.position(|x| x == b"\r\n\r\n")
+			.ok_or_else(|| DockerError::InvalidOutput(String::from("Malformed response")))?;
+		let head = String::from_utf8_lossy(&raw[..split]);
+		let body = &raw[split + 4..];
+
+		let mut lines = head.lines();
+		let status = lines
+			.next()
+			.and_then(|x| x.split(' ').nth(1))
+			.and_then(|x| x.parse().ok())
+			.ok_or_else(|| DockerError::InvalidOutput(String::from("Malformed status line")))?;
+
+		let chunked = lines.any(|x| {
+			let x = x.to_ascii_lowercase();
+			x.starts_with("transfer-encoding:") && x.contains("chunked")
+		});
+
+		let body = if chunked {
+			decode_chunked(body)
+		} else {
+			body.to_vec()
+		};
+
+		Ok(Self { status, body })
+	}
+
+	fn json(&self) -> Result<Value, DockerError> {
+		serde_json::from_slice(&self.body).map_err(|x| DockerError::InvalidOutput(x.to_string()))
+	}
+
+	/// Turn a non-success response into an [`ApiError`]
+	fn check(self) -> Result<Self, DockerError> {
+		if (200..300).contains(&self.status) {
+			return Ok(self);
+		}
+
+		let message = match self.json() {
+			Ok(x) => x["message"].as_str().map(String::from),
+			Err(_) => None,
+		}
+		.unwrap_or_else(|| String::from_utf8_lossy(&self.body).trim().to_string());
+
+		Err(DockerError::Api(ApiError {
+			status: self.status,
+			message,
+		}))
+	}
+}
+
+fn decode_chunked(mut body: &[u8]) -> Vec<u8> {
+	let mut output = vec![];
+
+	while let Some(end) = body.windows(2).position(|x| x == b"\r\n") {
+		let size = String::from_utf8_lossy(&body[..end]);
+		let size = usize::from_str_radix(size.split(';').next().unwrap_or("").trim(), 16);
+		let size = match size {
+			Ok(0) | Err(_) => break,
+			Ok(x) => x,
+		};
+
+		let start = end + 2;
+		let stop = (start + size).min(body.len());
+		output.extend_from_slice(&body[start..stop]);
+		body = body.get(stop + 2..).unwrap_or_default();
+	}
+
+	output
+}
+
+/// Split a multiplexed exec stream into stdout and stderr
+fn demultiplex(mut stream: &[u8]) -> (Vec<u8>, Vec<u8>) {
+	let (mut stdout, mut stderr) = (vec![], vec![]);
+
+	while stream.len() >= 8 {
+		let size = u32::from_be_bytes([stream[4], stream[5], stream[6], stream[7]]) as usize;
+		let stop = (8 + size).min(stream.len());
+
+		match stream[0] {
+			2 => stderr.extend_from_slice(&stream[8..stop]),
+			_ => stdout.extend_from_slice(&stream[8..stop]),
+		}
+
+		stream = &stream[stop..];
+	}
+
+	(stdout, stderr)
+}
+
+/// Percent-encode everything but unreserved characters
+pub(crate) fn encode(value: &str) -> String {
+	value
+		.bytes()
+		.map(|x| match x {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+				String::from(x as char)
+			}
+			_ => format!("%{x:02X}"),
+		})
+		.collect()
+}
+
+/// Split an image reference into the `fromImage` and `tag` pull parameters
+fn split_reference(path: &str) -> (&str, &str) {
+	if let Some((image, digest)) = path.split_once('@') {
+		return (image, digest);
+	}
+
+	match path.rsplit_once(':') {
+		Some((image, tag)) if !tag.contains('/') => (image, tag),
+		_ => (path, "latest"),
+	}
+}
+
+/// A runtime which talks to the Docker Engine API directly
+///
+/// Uses the unix socket at `/var/run/docker.sock`, or whatever `DOCKER_HOST`
+/// points at, rather than spawning a `docker` process for every operation.
+pub struct DockerApi<'a> {
+	name: &'a str,
+	path: &'a str,
+	settings: Settings,
+	endpoint: Endpoint,
+}
+
+impl DockerApi<'_> {
+	pub fn new<'a>(name: &'a str, path: &'a str, settings: Settings) -> DockerApi<'a> {
+		DockerApi::with_endpoint(name, path, settings, Endpoint::from_env())
+	}
+
+	pub fn with_endpoint<'a>(
+		name: &'a str,
+		path: &'a str,
+		settings: Settings,
+		endpoint: Endpoint,
+	) -> DockerApi<'a> {
+		DockerApi {
+			name,
+			path,
+			settings,
+			endpoint,
+		}
+	}
+
+	/// Remove containers created by corrator; see [`Docker::reap`]
+	///
+	/// [`Docker::reap`]: super::Docker::reap
+	pub fn reap(&self, run_id: Option<&str>) -> Result<Vec<String>, DockerError> {
+		let label = match run_id {
+			Some(x) => format!("{}={x}", runtime::LABEL),
+			None => String::from(runtime::LABEL),
+		};
+		let filters = json!({ "label": [label] }).to_string();
+		let containers = self
+			.endpoint
+			.request(
+				"GET",
+				&format!("/containers/json?all=true&filters={}", encode(&filters)),
+				None,
+			)?
+			.check()?
+			.json()?;
+
+		let mut names = vec![];
+		for container in containers.as_array().into_iter().flatten() {
+			let id = container["Id"]
+				.as_str()
+				.ok_or_else(|| DockerError::InvalidOutput(String::from("Missing container id")))?;
+			if run_id.is_none() && runtime::is_in_use(&container["Labels"]) {
+				continue;
+			}
+
+			self.endpoint
+				.request(
+					"DELETE",
+					&format!("/containers/{}?force=true", encode(id)),
+					None,
+				)?
+				.check()?;
+
+			// Engine names are prefixed with a slash, e.g., "/corrator-ubuntu-..."
+			let name = container["Names"][0].as_str().unwrap_or(id);
+			names.push(String::from(name.trim_start_matches('/')));
+		}
+
+		Ok(names)
+	}
+
+	fn platform_query(&self) -> String {
+		match &self.settings.platform {
+			Some(x) => format!("&platform={}", encode(x)),
+			None => String::new(),
+		}
+	}
+
+	/// Whether the image is present for the requested platform; a tag pulled
+	/// for another platform doesn't count
+	fn image_exists(&self) -> Result<bool, DockerError> {
+		let response =
+			self.endpoint
+				.request("GET", &format!("/images/{}/json", encode(self.path)), None)?;
+
+		if response.status == 404 {
+			return Ok(false);
+		}
+
+		let image = Image::from_inspect(&response.check()?.json()?, self.path);
+
+		Ok(self
+			.settings
+			.platform
+			.as_deref()
+			.is_none_or(|x| image.matches_platform(x)))
+	}
+
+	fn pull_image(&self) -> Result<(), DockerError> {
+		let (image, tag) = split_reference(self.path);
+		let auth = auth::registry_auth(self.path);
+		let headers: Vec<_> = auth
+			.iter()
+			.map(|x| ("X-Registry-Auth", x.as_str()))
+			.collect();
+		let response = self
+			.endpoint
+			.send(
+				"POST",
+				&format!(
+					"/images/create?fromImage={}&tag={}{}",
+					encode(image),
+					encode(tag),
+					self.platform_query()
+				),
+				&headers,
+				None,
+				self.settings.timeouts.pull(),
+			)?
+			.check()
+			.map_err(|err| self.pull_error(err))?;
+
+		// Pull failures part way through are reported inside the progress stream
+		for line in response.body.split(|x| *x == b'\n') {
+			if let Ok(Value::Object(x)) = serde_json::from_slice(line) {
+				if let Some(message) = x.get("error").and_then(Value::as_str) {
+					return Err(self.pull_error(DockerError::Api(ApiError {
+						status: response.status,
+						message: String::from(message),
+					})));
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	fn pull_error(&self, err: DockerError) -> DockerError {
+		match self.not_found_error(err) {
+			DockerError::Api(x) => DockerError::PullFailed {
+				image: String::from(self.path),
+				message: x.message,
+			},
+			x => x,
+		}
+	}
+
+	/// Report a missing file as such, rather than the container being missing
+	fn file_error(&self, path: &str, err: DockerError) -> DockerError {
+		match err {
+			DockerError::Api(x)
+				if x.status == 404 && !x.message.starts_with("No such container") =>
+			{
+				DockerError::FileNotFound(String::from(path))
+			}
+			x => x,
+		}
+	}
+
+	/// Report a missing image as such, leaving other errors alone
+	fn not_found_error(&self, err: DockerError) -> DockerError {
+		match err {
+			DockerError::Api(x) if x.status == 404 || is_not_found(&x.message) => {
+				DockerError::ImageNotFound(String::from(self.path))
+			}
+			x => x,
+		}
+	}
+}
+
+impl ContainerRuntime for DockerApi<'_> {
+	fn pull(&self) -> Result<(), DockerError> {
+		match self.settings.pull_policy {
+			PullPolicy::Always => self.pull_image(),
+			PullPolicy::IfMissing if self.image_exists()? => Ok(()),
+			PullPolicy::IfMissing => self.pull_image(),
+			PullPolicy::Never if self.image_exists()? => Ok(()),
+			PullPolicy::Never => Err(DockerError::ImageNotFound(String::from(self.path))),
+		}
+	}
+
+	fn inspect(&self) -> Result<Image, DockerError> {
+		// By the container's image ID, since the tag may since have been
+		// pulled for another platform
+		let id = self
+			.endpoint
+			.request(
+				"GET",
+				&format!("/containers/{}/json", encode(self.name)),
+				None,
+			)?
+			.check()?
+			.json()?["Image"]
+			.as_str()
+			.map(String::from)
+			.ok_or_else(|| DockerError::InvalidOutput(String::from("Missing image id")))?;
+		let inspect = self
+			.endpoint
+			.request("GET", &format!("/images/{}/json", encode(&id)), None)?
+			.check()?
+			.json()?;
+
+		Ok(Image::from_inspect(&inspect, self.path))
+	}
+
+	fn run(&self) -> Result<(), DockerError> {
+		if !self.settings.run_args.is_empty() {
+			return Err(DockerError::Unsupported(String::from(
+				"run_args need the docker or podman runtime",
+			)));
+		}
+
+		// A null entrypoint keeps the image's own
+		let entrypoint = match self.settings.keep_entrypoint {
+			true => Value::Null,
+			false => json!([""]),
+		};
+		let body = json!({
+			"Image": self.path,
+			"Entrypoint": entrypoint,
+			"Cmd": ["sh"],
+			"Tty": true,
+			"OpenStdin": true,
+			"Labels": runtime::container_labels()
+				.into_iter()
+				.collect::<BTreeMap<_, _>>(),
+			"Env": self.settings.env_pairs(),
+			"User": self.settings.user.as_deref().unwrap_or_default(),
+			"WorkingDir": self.settings.workdir.as_deref().unwrap_or_default(),
+			"HostConfig": {
+				"NetworkMode": self.settings.network(),
+				"Binds": self.settings.volumes,
+			},
+		});
+		self.endpoint
+			.request_within(
+				"POST",
+				&format!(
+					"/containers/create?name={}{}",
+					encode(self.name),
+					self.platform_query()
+				),
+				Some(&body),
+				self.settings.timeouts.start(),
+			)?
+			.check()
+			.map_err(|err| self.not_found_error(err))?;
+
+		if self.settings.probe == Probe::Files {
+			return Ok(());
+		}
+
+		self.endpoint
+			.request_within(
+				"POST",
+				&format!("/containers/{}/start", encode(self.name)),
+				None,
+				self.settings.timeouts.start(),
+			)?
+			.check()?;
+
+		Ok(())
+	}
+
+	fn execute(
+		&self,
+		args: &[String],
+		timeout: Option<Duration>,
+	) -> Result<ExecOutput, DockerError> {
+		let body = json!({
+			"AttachStdout": true,
+			"AttachStderr": true,
+			"Cmd": args,
+			"Env": self.settings.env_pairs(),
+			"User": self.settings.user.as_deref().unwrap_or_default(),
+			"WorkingDir": self.settings.workdir.as_deref().unwrap_or_default(),
+		});
+		let id = self
+			.endpoint
+			.request(
+				"POST",
+				&format!("/containers/{}/exec", encode(self.name)),
+				Some(&body),
+			)?
+			.check()?
+			.json()?["Id"]
+			.as_str()
+			.map(String::from)
+			.ok_or_else(|| DockerError::InvalidOutput(String::from("Missing exec id")))?;
+
+		let body = json!({ "Detach": false, "Tty": false });
+		let response = self
+			.endpoint
+			.request_within("POST", &format!("/exec/{id}/start"), Some(&body), timeout)?
+			.check()?;
+		let (stdout, stderr) = demultiplex(&response.body);
+
+		let status = self
+			.endpoint
+			.request("GET", &format!("/exec/{id}/json"), None)?
+			.check()?
+			.json()?["ExitCode"]
+			.as_i64()
+			.and_then(|x| i32::try_from(x).ok());
+
+		Ok(ExecOutput {
+			stdout: String::from_utf8(stdout)?,
+			stderr: String::from_utf8(stderr)?,
+			status,
+		})
+	}
+
+	fn read_file(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<u8>, DockerError> {
+		runtime::follow_links(path, |path| {
+			let response = self
+				.endpoint
+				.request_within(
+					"GET",
+					&format!(
+						"/containers/{}/archive?path={}",
+						encode(self.name),
+						encode(path)
+					),
+					None,
+					timeout,
+				)?
+				.check()
+				.map_err(|err| self.file_error(path, err))?;
+
+			runtime::unarchive_file(&response.body)
+		})
+	}
+
+	fn read_dir(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<Vec<u8>>, DockerError> {
+		let response = self
+			.endpoint
+			.request_within(
+				"GET",
+				&format!(
+					"/containers/{}/archive?path={}",
+					encode(self.name),
+					encode(path)
+				),
+				None,
+				timeout,
+			)?
+			.check()
+			.map_err(|err| self.file_error(path, err))?;
+
+		runtime::unarchive_dir(&response.body)
+	}
+
+	fn stop(&self) -> Result<(), DockerError> {
+		self.endpoint
+			.request(
+				"DELETE",
+				&format!("/containers/{}?force=true", encode(self.name)),
+				None,
+			)?
+			.check()?;
+
+		Ok(())
+	}
+
+	fn clean(&self) -> Result<(), DockerError> {
+		self.endpoint
+			.request(
+				"DELETE",
+				&format!("/images/{}?force=true", encode(self.path)),
+				None,
+			)?
+			.check()?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{demultiplex, split_reference, DockerApi, Endpoint};
+	use crate::{
+		docker::DockerError,
+		runtime::{ContainerRuntime, Probe, PullPolicy, Settings},
+	};
+	use serde_json::{json, Value};
+	use std::{
+		io::{Read, Write},
+		os::unix::net::UnixListener,
+		path::PathBuf,
+		sync::{Arc, Mutex},
+		thread,
+	};
+
+	type Route = (&'static str, u16, Vec<u8>);
+
+	/// A minimal Engine API stand-in, serving canned responses by path prefix
+	struct MockDaemon {
+		socket: PathBuf,
+		requests: Arc<Mutex<Vec<String>>>,
+		bodies: Arc<Mutex<Vec<String>>>,
+	}
+
+	impl MockDaemon {
+		fn start(test: &str, routes: Vec<Route>) -> Self {
+			let socket =
+				std::env::temp_dir().join(format!("corrator-{test}-{}.sock", std::process::id()));
+			drop(std::fs::remove_file(&socket));
+
+			let listener = UnixListener::bind(&socket).unwrap();
+			let requests = Arc::new(Mutex::new(vec![]));
+			let bodies = Arc::new(Mutex::new(vec![]));
+			let log = Arc::clone(&requests);
+			let body_log = Arc::clone(&bodies);
+
+			thread::spawn(move || {
+				for stream in listener.incoming() {
+					let mut stream = stream.unwrap();
+					let request = read_request(&mut stream);
+					let line = request.lines().next().unwrap_or_default().to_string();
+					log.lock().unwrap().push(line.clone());
+					let body = request.split_once("\r\n\r\n").unwrap_or_default().1;
+					body_log.lock().unwrap().push(body.to_string());
+
+					let path = line.split(' ').nth(1).unwrap_or_default();
+					let (status, body) = routes
+						.iter()
+						.find(|(prefix, _, _)| path.starts_with(prefix))
+						.map(|(_, status, body)| (*status, body.clone()))
+						.unwrap_or((404, br#"{"message":"no route"}"#.to_vec()));
+
+					let head = format!(
+						"HTTP/1.1 {status} Mock\r\nContent-Length: {}\r\n\r\n",
+						body.len()
+					);
+					stream.write_all(head.as_bytes()).unwrap();
+					stream.write_all(&body).unwrap();
+				}
+			});
+
+			Self {
+				socket,
+				requests,
+				bodies,
+			}
+		}
+
+		fn api<'a>(&self, name: &'a str, path: &'a str, settings: Settings) -> DockerApi<'a> {
+			DockerApi::with_endpoint(name, path, settings, Endpoint::Unix(self.socket.clone()))
+		}
+
+		fn requests(&self) -> Vec<String> {
+			self.requests.lock().unwrap().clone()
+		}
+
+		fn bodies(&self) -> Vec<Value> {
+			let bodies = self.bodies.lock().unwrap();
+			bodies
+				.iter()
+				.map(|x| serde_json::from_str(x).unwrap_or_default())
+				.collect()
+		}
+	}
+
+	impl Drop for MockDaemon {
+		fn drop(&mut self) {
+			drop(std::fs::remove_file(&self.socket));
+		}
+	}
+
+	fn read_request(stream: &mut impl Read) -> String {
+		let mut raw = vec![];
+		let mut buffer = [0; 1024];
+
+		loop {
+			let read = stream.read(&mut buffer).unwrap();
+			raw.extend_from_slice(&buffer[..read]);
+
+			let text = String::from_utf8_lossy(&raw).to_string();
+			if let Some((head, body)) = text.split_once("\r\n\r\n") {
+				let length = head
+					.lines()
+					.find_map(|x| x.strip_prefix("Content-Length: "))
+					.and_then(|x| x.parse::<usize>().ok())
+					.unwrap_or(0);
+				if body.len() >= length || read == 0 {
+					return text;
+				}
+			}
+		}
+	}
+
+	fn frame(stream: u8, data: &[u8]) -> Vec<u8> {
+		let mut frame = vec![stream, 0, 0, 0];
+		frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+		frame.extend_from_slice(data);
+		frame
+	}
+
+	#[test]
+	fn lifecycle_against_mock_daemon() {
+		let mut stream = frame(1, b"GNU bash, version 5.1.16\n");
+		stream.extend(frame(2, b"a warning\n"));
+
+		let daemon = MockDaemon::start(
+			"lifecycle",
+			vec![
+				(
+					"/v1.41/images/create",
+					200,
+					br#"{"status":"done"}"#.to_vec(),
+				),
+				("/v1.41/containers/create", 201, br#"{"Id":"abc"}"#.to_vec()),
+				("/v1.41/containers/test/start", 204, vec![]),
+				(
+					"/v1.41/containers/test/exec",
+					201,
+					br#"{"Id":"e1"}"#.to_vec(),
+				),
+				("/v1.41/exec/e1/start", 200, stream),
+				("/v1.41/exec/e1/json", 200, br#"{"ExitCode":3}"#.to_vec()),
+				("/v1.41/containers/test?force=true", 204, vec![]),
+			],
+		);
+		let settings = Settings {
+			platform: Some(String::from("linux/arm64")),
+			..Default::default()
+		};
+		let api = daemon.api("test", "ubuntu:22.04", settings);
+
+		api.pull().unwrap();
+		api.run().unwrap();
+		let exec = api
+			.execute(&[String::from("bash"), String::from("--version")], None)
+			.unwrap();
+		api.stop().unwrap();
+
+		assert_eq!(exec.status, Some(3));
+		assert_eq!(exec.stdout, "GNU bash, version 5.1.16\n");
+		assert_eq!(exec.stderr, "a warning\n");
+		assert_eq!(
+			daemon.requests()[0],
+			"POST /v1.41/images/create?fromImage=ubuntu&tag=22.04&platform=linux%2Farm64 HTTP/1.1"
+		);
+		assert_eq!(daemon.requests().len(), 7);
+	}
+
+	#[test]
+	fn reaps_labelled_containers() {
+		let daemon = MockDaemon::start(
+			"reap",
+			vec![
+				(
+					"/v1.41/containers/json",
+					200,
+					json!([
+						{ "Id": "abc", "Names": ["/corrator-ubuntu-1"] },
+						{
+							"Id": "def",
+							"Names": ["/corrator-ubuntu-2"],
+							"Labels": { "rs.corrator.pid": std::process::id().to_string() },
+						},
+						{
+							"Id": "ghi",
+							"Names": ["/corrator-ubuntu-3"],
+							"Labels": {
+								"rs.corrator.pid": "4294967295",
+								"rs.corrator.host": "another-host",
+							},
+						},
+					])
+					.to_string()
+					.into_bytes(),
+				),
+				("/v1.41/containers/abc?force=true", 204, vec![]),
+				("/v1.41/containers/def?force=true", 204, vec![]),
+				("/v1.41/containers/ghi?force=true", 204, vec![]),
+			],
+		);
+		let api = daemon.api("", "", Settings::default());
+
+		let names = api.reap(Some("run")).unwrap();
+
+		assert_eq!(
+			names,
+			vec![
+				"corrator-ubuntu-1",
+				"corrator-ubuntu-2",
+				"corrator-ubuntu-3"
+			]
+		);
+		assert_eq!(
+			daemon.requests()[0],
+			"GET /v1.41/containers/json?all=true&filters=%7B%22label%22%3A%5B%22rs.corrator.run%3Drun%22%5D%7D HTTP/1.1"
+		);
+		assert_eq!(daemon.requests().len(), 4);
+
+		// Without a run, containers of a process which may still be running,
+		// or of another host, are kept
+		let names = api.reap(None).unwrap();
+
+		assert_eq!(names, vec!["corrator-ubuntu-1"]);
+	}
+
+	#[test]
+	fn creates_containers_with_settings() {
+		let daemon = MockDaemon::start(
+			"settings",
+			vec![
+				("/v1.41/containers/create", 201, br#"{"Id":"abc"}"#.to_vec()),
+				("/v1.41/containers/test/start", 204, vec![]),
+			],
+		);
+		let settings = Settings {
+			env: [(String::from("JAVA_HOME"), String::from("/opt/java"))].into(),
+			user: Some(String::from("nobody")),
+			volumes: vec![String::from("/opt/certs:/certs:ro")],
+			..Default::default()
+		};
+
+		daemon.api("test", "ubuntu", settings).run().unwrap();
+
+		let body = &daemon.bodies()[0];
+		assert_eq!(
+			body["Labels"]["rs.corrator.pid"],
+			std::process::id().to_string()
+		);
+		assert_eq!(body["Env"], json!(["JAVA_HOME=/opt/java"]));
+		assert_eq!(body["User"], "nobody");
+		assert_eq!(body["HostConfig"]["NetworkMode"], "none");
+		assert_eq!(body["HostConfig"]["Binds"], json!(["/opt/certs:/certs:ro"]));
+
+		let settings = Settings {
+			run_args: vec![String::from("--privileged")],
+			..Default::default()
+		};
+		let err = daemon.api("test", "ubuntu", settings).run().unwrap_err();
+		assert!(matches!(err, DockerError::Unsupported(_)));
+	}
+
+	#[test]
+	fn pull_errors_are_reported() {
+		let daemon = MockDaemon::start(
+			"pull-error",
+			vec![(
+				"/v1.41/images/create",
+				200,
+				b"{\"status\":\"Pulling\"}\n{\"error\":\"manifest unknown\"}\n".to_vec(),
+			)],
+		);
+		let api = daemon.api("test", "ubuntu:nope", Settings::default());
+
+		let err = api.pull().unwrap_err();
+
+		assert!(matches!(err, DockerError::ImageNotFound(x) if x == "ubuntu:nope"));
+	}
+
+	#[test]
+	fn never_pull_requires_local_image() {
+		let daemon = MockDaemon::start(
+			"never-pull",
+			vec![(
+				"/v1.41/images/ghcr.io%2Forg%2Fapp%3A1.0/json",
+				404,
+				br#"{"message":"No such image: ghcr.io/org/app:1.0"}"#.to_vec(),
+			)],
+		);
+		let settings = Settings {
+			pull_policy: PullPolicy::Never,
+			..Default::default()
+		};
+		let api = daemon.api("test", "ghcr.io/org/app:1.0", settings);
+
+		let err = api.pull().unwrap_err();
+
+		assert!(matches!(err, DockerError::ImageNotFound(_)));
+		assert_eq!(
+			daemon.requests(),
+			vec!["GET /v1.41/images/ghcr.io%2Forg%2Fapp%3A1.0/json HTTP/1.1"]
+		);
+	}
+
+	#[test]
+	fn inspects_the_image_containers_were_created_from() {
+		let daemon = MockDaemon::start(
+			"inspect",
+			vec![
+				(
+					"/v1.41/containers/test/json",
+					200,
+					br#"{"Image":"sha256:arm"}"#.to_vec(),
+				),
+				(
+					"/v1.41/images/sha256%3Aarm/json",
+					200,
+					br#"{"Id":"sha256:arm","Architecture":"arm64"}"#.to_vec(),
+				),
+			],
+		);
+		let api = daemon.api("test", "ubuntu", Settings::default());
+
+		let image = api.inspect().unwrap();
+
+		assert_eq!(image.id, "sha256:arm");
+		assert_eq!(image.architecture.as_deref(), Some("arm64"));
+		assert_eq!(
+			daemon.requests(),
+			vec![
+				"GET /v1.41/containers/test/json HTTP/1.1",
+				"GET /v1.41/images/sha256%3Aarm/json HTTP/1.1"
+			]
+		);
+	}
+
+	#[test]
+	fn images_for_another_platform_are_missing() {
+		let daemon = MockDaemon::start(
+			"other-platform",
+			vec![(
+				"/v1.41/images/ubuntu/json",
+				200,
+				br#"{"Id":"sha256:amd","Os":"linux","Architecture":"amd64"}"#.to_vec(),
+			)],
+		);
+		let settings = |platform: &str| Settings {
+			pull_policy: PullPolicy::Never,
+			platform: Some(String::from(platform)),
+			..Default::default()
+		};
+
+		assert!(daemon
+			.api("test", "ubuntu", settings("linux/amd64"))
+			.pull()
+			.is_ok());
+		assert!(matches!(
+			daemon.api("test", "ubuntu", settings("linux/arm64")).pull(),
+			Err(DockerError::ImageNotFound(_))
+		));
+	}
+
+	#[test]
+	fn reads_files_following_symlinks() {
+		let daemon = MockDaemon::start(
+			"read-file",
+			vec![
+				("/v1.41/containers/create", 201, br#"{"Id":"abc"}"#.to_vec()),
+				(
+					"/v1.41/containers/test/archive?path=%2Fetc%2Fos-release",
+					200,
+					archive("os-release", None, Some("../usr/lib/os-release")),
+				),
+				(
+					"/v1.41/containers/test/archive?path=%2Fusr%2Flib%2Fos-release",
+					200,
+					archive("os-release", Some(b"ID=debian\n"), None),
+				),
+			],
+		);
+		let settings = Settings {
+			probe: Probe::Files,
+			..Default::default()
+		};
+		let api = daemon.api("test", "gcr.io/distroless/static", settings);
+
+		api.run().unwrap();
+		let contents = api.read_file("/etc/os-release", None).unwrap();
+
+		assert_eq!(contents, b"ID=debian\n");
+		// Containers probed by files are created but never started
+		assert!(!daemon.requests().iter().any(|x| x.contains("/start")));
+	}
+
+	#[test]
+	fn reads_directories() {
+		let mut builder = tar::Builder::new(vec![]);
+		for (name, contents) in [
+			("status.d/libc6", &b"Package: libc6\n"[..]),
+			("status.d/nested/file", b"skipped"),
+			("status.d/tzdata", b"Package: tzdata\n"),
+		] {
+			let mut header = tar::Header::new_gnu();
+			header.set_size(contents.len() as u64);
+			builder.append_data(&mut header, name, contents).unwrap();
+		}
+		let daemon = MockDaemon::start(
+			"read-dir",
+			vec![(
+				"/v1.41/containers/test/archive?path=%2Fvar%2Flib%2Fdpkg%2Fstatus.d",
+				200,
+				builder.into_inner().unwrap(),
+			)],
+		);
+		let api = daemon.api("test", "gcr.io/distroless/base", Settings::default());
+
+		let files = api.read_dir("/var/lib/dpkg/status.d", None).unwrap();
+
+		assert_eq!(files, [&b"Package: libc6\n"[..], b"Package: tzdata\n"]);
+	}
+
+	#[test]
+	fn missing_files_are_not_found() {
+		let daemon = MockDaemon::start(
+			"missing-file",
+			vec![
+				(
+					"/v1.41/containers/test/",
+					404,
+					br#"{"message":"Could not find the file /etc/os-release in container test"}"#
+						.to_vec(),
+				),
+				(
+					"/v1.41/containers/gone/",
+					404,
+					br#"{"message":"No such container: gone"}"#.to_vec(),
+				),
+			],
+		);
+
+		let api = daemon.api("test", "ubuntu", Settings::default());
+		assert!(matches!(
+			api.read_file("/etc/os-release", None),
+			Err(DockerError::FileNotFound(_))
+		));
+		assert!(matches!(
+			api.read_dir("/var/lib/dpkg/status.d", None),
+			Err(DockerError::FileNotFound(_))
+		));
+
+		let api = daemon.api("gone", "ubuntu", Settings::default());
+		assert!(matches!(
+			api.read_file("/etc/os-release", None),
+			Err(DockerError::Api(_))
+		));
+	}
+
+	fn archive(name: &str, contents: Option<&[u8]>, link: Option<&str>) -> Vec<u8> {
+		let mut builder = tar::Builder::new(vec![]);
+		let mut header = tar::Header::new_gnu();
+
+		match (contents, link) {
+			(_, Some(link)) => {
+				header.set_entry_type(tar::EntryType::Symlink);
+				header.set_size(0);
+				builder.append_link(&mut header, name, link).unwrap();
+			}
+			(Some(contents), None) => {
+				header.set_size(contents.len() as u64);
+				builder.append_data(&mut header, name, contents).unwrap();
+			}
+			(None, None) => unreachable!(),
+		}
+
+		builder.into_inner().unwrap()
+	}
+
+	#[test]
+	fn demultiplexes_streams() {
+		let mut stream = frame(2, b"err");
+		stream.extend(frame(1, b"out"));
+
+		assert_eq!(demultiplex(&stream), (b"out".to_vec(), b"err".to_vec()));
+	}
+
+	#[test]
+	fn splits_references() {
+		assert_eq!(split_reference("ubuntu"), ("ubuntu", "latest"));
+		assert_eq!(
+			split_reference("localhost:5000/app:1.0"),
+			("localhost:5000/app", "1.0")
+		);
+		assert_eq!(
+			split_reference("localhost:5000/app"),
+			("localhost:5000/app", "latest")
+		);
+		assert_eq!(split_reference("app@sha256:abc"), ("app", "sha256:abc"));
+	}
+}
@@ -0,0 +1,153 @@
+//! Registry credentials, as stored by `docker login`
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE};
+use base64::Engine;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::{env, fs};
+
+use crate::podman::qualify;
+
+/// The key Docker Hub credentials are stored under
+const DOCKER_HUB: &str = "https://index.docker.io/v1/";
+
+/// The `X-Registry-Auth` header value for pulling an image, if there are
+/// credentials for its registry
+///
+/// Credentials come from the docker config, i.e., `$DOCKER_CONFIG/config.json`
+/// or `~/.docker/config.json`, including any credential helpers it names.
+pub(crate) fn registry_auth(path: &str) -> Option<String> {
+	let config = fs::read(config_path()?).ok()?;
+	let config: Value = serde_json::from_slice(&config).ok()?;
+
+	let credentials = credentials(&config, &registry(path))?;
+	Some(URL_SAFE.encode(credentials.to_string()))
+}
+
+fn config_path() -> Option<PathBuf> {
+	match env::var_os("DOCKER_CONFIG") {
+		Some(x) => Some(PathBuf::from(x).join("config.json")),
+		None => Some(
+			directories::BaseDirs::new()?
+				.home_dir()
+				.join(".docker/config.json"),
+		),
+	}
+}
+
+/// The registry an image is pulled from, e.g., "ghcr.io" for "ghcr.io/org/app"
+fn registry(path: &str) -> String {
+	let qualified = qualify(path);
+	let domain = qualified.split('/').next().unwrap_or_default();
+
+	normalize(domain)
+}
+
+/// A registry host without any scheme or path, with Docker Hub's aliases
+/// treated as one
+fn normalize(key: &str) -> String {
+	let host = key.split_once("://").map_or(key, |(_, x)| x);
+	let host = host.split('/').next().unwrap_or_default();
+
+	match host {
+		"index.docker.io" | "registry-1.docker.io" => String::from("docker.io"),
+		x => String::from(x),
+	}
+}
+
+/// Credentials for a registry in the shape `X-Registry-Auth` expects
+fn credentials(config: &Value, registry: &str) -> Option<Value> {
+	let server = match registry {
+		"docker.io" => DOCKER_HUB,
+		x => x,
+	};
+
+	let helper = config["credHelpers"]
+		.as_object()
+		.and_then(|x| x.iter().find(|(key, _)| normalize(key) == registry))
+		.map(|(_, x)| x)
+		.or_else(|| config.get("credsStore"))
+		.and_then(Value::as_str);
+	if let Some(helper) = helper {
+		return from_helper(helper, server);
+	}
+
+	let (_, auth) = config["auths"]
+		.as_object()?
+		.iter()
+		.find(|(key, _)| normalize(key) == registry)?;
+
+	if let Some(token) = auth["identitytoken"].as_str() {
+		return Some(json!({ "identitytoken": token, "serveraddress": server }));
+	}
+
+	let decoded = STANDARD.decode(auth["auth"].as_str()?).ok()?;
+	let (username, password) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+
+	Some(json!({ "username": username, "password": password, "serveraddress": server }))
+}
+
+/// Ask a credential helper, e.g., `docker-credential-pass`, for credentials
+fn from_helper(helper: &str, server: &str) -> Option<Value> {
+	let mut child = Command::new(format!("docker-credential-{helper}"))
+		.arg("get")
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()
+		.ok()?;
+	child.stdin.take()?.write_all(server.as_bytes()).ok()?;
+
+	let output = child.wait_with_output().ok()?;
+	if !output.status.success() {
+		return None;
+	}
+
+	let credentials: Value = serde_json::from_slice(&output.stdout).ok()?;
+	let username = credentials["Username"].as_str()?;
+	let secret = credentials["Secret"].as_str()?;
+
+	// Helpers store identity tokens under this placeholder username
+	match username {
+		"<token>" => Some(json!({ "identitytoken": secret, "serveraddress": server })),
+		x => Some(json!({ "username": x, "password": secret, "serveraddress": server })),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn finds_registries() {
+		assert_eq!(registry("ubuntu:22.04"), "docker.io");
+		assert_eq!(registry("ghcr.io/org/app"), "ghcr.io");
+		assert_eq!(registry("localhost:5000/app@sha256:abc"), "localhost:5000");
+	}
+
+	#[test]
+	fn reads_stored_credentials() {
+		let config = json!({
+			"auths": {
+				"https://index.docker.io/v1/": { "auth": STANDARD.encode("user:pa:ss") },
+				"ghcr.io": { "identitytoken": "token" }
+			}
+		});
+
+		assert_eq!(
+			credentials(&config, "docker.io"),
+			Some(json!({
+				"username": "user",
+				"password": "pa:ss",
+				"serveraddress": DOCKER_HUB
+			}))
+		);
+		assert_eq!(
+			credentials(&config, "ghcr.io"),
+			Some(json!({ "identitytoken": "token", "serveraddress": "ghcr.io" }))
+		);
+		assert_eq!(credentials(&config, "quay.io"), None);
+	}
+}
@@ -4,11 +4,21 @@ use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::error::Error;
+use std::num::NonZeroUsize;
+use std::time::Duration;
 
 pub mod application;
+pub mod archive;
 pub mod container;
 pub mod docker;
 pub mod end_of_life;
+pub mod package;
+pub mod podman;
+pub mod runtime;
+pub mod sbom;
+pub mod version;
+
+use runtime::ContainerRuntime;
 
 pub type ContainerMap = BTreeMap<String, container::Container>;
 pub type ApplicationMap = BTreeMap<String, application::Application>;
@@ -21,8 +31,19 @@ pub enum FilterFunction {
 	All,
 }
 
+/// Container engine used to run containers
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Runtime {
+	#[default]
+	Docker,
+	Podman,
+	/// Talk to the Docker Engine API directly rather than via the docker CLI
+	DockerApi,
+}
+
 /// Various runtime options
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Options {
 	/// Remove the docker image after execution
 	clean_after_query: bool,
@@ -32,6 +53,27 @@ pub struct Options {
 	filter_function: FilterFunction,
 	/// Container names to filter by
 	names: Option<Vec<String>>,
+	/// Container engine to use, e.g., "podman"
+	#[serde(default)]
+	runtime: Runtime,
+	/// Alternative podman storage root, e.g., for rootless hosts
+	podman_root: Option<String>,
+	/// When to pull images, unless a container overrides it
+	#[serde(default)]
+	pull_policy: runtime::PullPolicy,
+	/// Limits on each step of a query, unless a container overrides them
+	#[serde(default)]
+	timeouts: runtime::Timeouts,
+	/// How to retry pulls and version commands which fail transiently
+	#[serde(default)]
+	retries: runtime::Retries,
+	/// How many containers to query at once; defaults to one per CPU
+	max_parallel: Option<NonZeroUsize>,
+	/// How many images to pull at once, within `max_parallel`; defaults to no limit
+	max_parallel_pulls: Option<NonZeroUsize>,
+	/// List every installed package, unless a container overrides it
+	#[serde(default)]
+	inventory: bool,
 }
 
 impl Options {
@@ -46,8 +88,84 @@ impl Options {
 			tags,
 			names,
 			filter_function,
+			..Default::default()
 		}
 	}
+
+	/// Select the container engine to use
+	pub fn with_runtime(mut self, runtime: Runtime) -> Self {
+		self.runtime = runtime;
+		self
+	}
+
+	/// Set when to pull images, unless a container overrides it
+	pub fn with_pull_policy(mut self, pull_policy: runtime::PullPolicy) -> Self {
+		self.pull_policy = pull_policy;
+		self
+	}
+
+	/// Set limits on each step of a query, unless a container overrides them
+	pub fn with_timeouts(mut self, timeouts: runtime::Timeouts) -> Self {
+		self.timeouts = timeouts;
+		self
+	}
+
+	/// Set how to retry pulls and version commands which fail transiently
+	pub fn with_retries(mut self, retries: runtime::Retries) -> Self {
+		self.retries = retries;
+		self
+	}
+
+	/// Limit how many containers are queried, and how many images pulled, at once
+	pub fn with_max_parallel(
+		mut self,
+		max_parallel: Option<NonZeroUsize>,
+		max_parallel_pulls: Option<NonZeroUsize>,
+	) -> Self {
+		self.max_parallel = max_parallel;
+		self.max_parallel_pulls = max_parallel_pulls;
+		self
+	}
+
+	/// Set whether to list every installed package, unless a container overrides it
+	pub fn with_inventory(mut self, inventory: bool) -> Self {
+		self.inventory = inventory;
+		self
+	}
+
+	/// Set an alternative podman storage root
+	pub fn with_podman_root(mut self, podman_root: Option<String>) -> Self {
+		self.podman_root = podman_root;
+		self
+	}
+}
+
+/// Remove containers left behind by corrator, returning their names
+///
+/// Only containers from the given run (see [`runtime::run_id`]) are removed
+/// if one is given, otherwise those from any run which has since exited are.
+pub fn cleanup(
+	options: &Options,
+	run_id: Option<&str>,
+) -> Result<Vec<String>, docker::DockerError> {
+	let settings = runtime::Settings::default();
+
+	match options.runtime {
+		Runtime::Docker => docker::Docker::new("", "", settings).reap(run_id),
+		Runtime::Podman => {
+			podman::Podman::new("", "", settings, options.podman_root.as_deref()).reap(run_id)
+		}
+		Runtime::DockerApi => docker::api::DockerApi::new("", "", settings).reap(run_id),
+	}
+}
+
+/// Config entries which can share a single container
+struct Group<'a> {
+	path: &'a str,
+	settings: runtime::Settings,
+	entries: Vec<(&'a String, &'a container::Container)>,
+	/// Whether the image may be removed afterwards, i.e., no later group uses it
+	clean: bool,
 }
 
 /// Runtime config required to run the app
@@ -90,11 +208,133 @@ impl Config {
 	/// config.run();
 	/// ```
 	pub fn run(&self) -> Result<Vec<container::Status>, Box<dyn Error>> {
-		Ok(self
-			.containers
-			.par_iter()
-			.map(|(name, container)| self.container_status(name, container))
-			.collect())
+		let groups = self.groups();
+
+		// A dedicated pool, so the limit doesn't affect other users of rayon's global one
+		let pool = rayon::ThreadPoolBuilder::new()
+			.num_threads(self.options.max_parallel.map_or(0, NonZeroUsize::get))
+			.build()?;
+		let pulls = self
+			.options
+			.max_parallel_pulls
+			.map(|x| runtime::Semaphore::new(x.get()));
+
+		// Groups sharing an image are queried one after another, since pulling
+		// one platform retags the image out from under another
+		let mut chains: Vec<Vec<&Group>> = vec![];
+		for group in &groups {
+			match chains.iter_mut().find(|x| x[0].path == group.path) {
+				Some(x) => x.push(group),
+				None => chains.push(vec![group]),
+			}
+		}
+
+		let mut statuses: Vec<container::Status> = pool.install(|| {
+			chains
+				.par_iter()
+				.flat_map_iter(|chain| {
+					chain.iter().flat_map(|group| {
+						let (name, container) = group.entries[0];
+						let instance_name =
+							runtime::instance_name(name, group.settings.platform.as_deref());
+						let instance =
+							self.runtime(&instance_name, container, group.settings.clone());
+
+						self.group_status(group, pulls.as_ref(), instance.as_ref())
+					})
+				})
+				.collect()
+		});
+
+		// Report in config order, rather than grouped by image
+		statuses.sort_by(|a, b| a.name.cmp(&b.name));
+		Ok(statuses)
+	}
+
+	/// Gather config entries into those which can share a container
+	///
+	/// Each platform of a container is queried separately, while entries
+	/// with the same image and settings share a single container.
+	fn groups(&self) -> Vec<Group<'_>> {
+		let mut groups: Vec<Group> = vec![];
+
+		for (name, container) in &self.containers {
+			for platform in container.platforms() {
+				let settings = self.settings(container, platform.as_deref());
+				let group = groups
+					.iter_mut()
+					.find(|x| x.path == container.path && x.settings == settings);
+
+				match group {
+					Some(x) => x.entries.push((name, container)),
+					None => groups.push(Group {
+						path: &container.path,
+						settings,
+						entries: vec![(name, container)],
+						clean: false,
+					}),
+				}
+			}
+		}
+
+		for i in 0..groups.len() {
+			groups[i].clean = self.options.clean_after_query
+				&& !groups[i + 1..].iter().any(|x| x.path == groups[i].path);
+		}
+
+		groups
+	}
+
+	fn runtime<'a>(
+		&'a self,
+		name: &'a str,
+		container: &'a container::Container,
+		settings: runtime::Settings,
+	) -> Box<dyn ContainerRuntime + 'a> {
+		if let Some(source) = archive::Source::parse(&container.path) {
+			return Box::new(archive::Archive::new(source, settings.platform));
+		}
+
+		match self.options.runtime {
+			Runtime::Docker => Box::new(docker::Docker::new(name, &container.path, settings)),
+			Runtime::Podman => Box::new(podman::Podman::new(
+				name,
+				&container.path,
+				settings,
+				self.options.podman_root.as_deref(),
+			)),
+			Runtime::DockerApi => {
+				Box::new(docker::api::DockerApi::new(name, &container.path, settings))
+			}
+		}
+	}
+
+	/// Resolve a container's runtime settings, falling back to global options
+	fn settings(
+		&self,
+		container: &container::Container,
+		platform: Option<&str>,
+	) -> runtime::Settings {
+		runtime::Settings {
+			pull_policy: container.pull_policy.unwrap_or(self.options.pull_policy),
+			timeouts: container.timeouts.or(self.options.timeouts),
+			platform: platform.map(String::from),
+			digest: container.expected_digest().map(String::from),
+			env: container.env.clone(),
+			user: container.user.clone(),
+			workdir: container.workdir.clone(),
+			network: container.network.clone(),
+			volumes: container.volumes.clone(),
+			run_args: container.run_args.clone(),
+			keep_entrypoint: container.keep_entrypoint,
+			init: container.init.clone(),
+			inventory: container.inventory.unwrap_or(self.options.inventory),
+			// Offline images can't run anything, so only files can be probed
+			probe: match archive::Source::parse(&container.path) {
+				Some(_) => runtime::Probe::Files,
+				None => container.probe,
+			},
+		}
 	}
 
 	fn filter_by_tags(
@@ -127,78 +367,495 @@ impl Config {
 		}
 	}
 
+	/// Query a group's entries in one container, splitting the results back out
+	fn group_status(
+		&self,
+		group: &Group,
+		pulls: Option<&runtime::Semaphore>,
+		instance: &dyn ContainerRuntime,
+	) -> Vec<container::Status> {
+		let apps: Vec<String> = group
+			.entries
+			.iter()
+			.flat_map(|(_, container)| container.apps.iter().cloned())
+			.collect();
+		let (name, _) = group.entries[0];
+		let shared =
+			self.container_status(name, apps, &group.settings, group.clean, pulls, instance);
+
+		group
+			.entries
+			.iter()
+			.map(|(name, container)| {
+				let mut apps: Vec<application::Status> = shared
+					.apps
+					.iter()
+					.filter(|x| container.apps.contains(&x.name))
+					.map(|x| application::Status {
+						check: self
+							.constraints(container, &x.name)
+							.check(x.version.as_deref()),
+						..x.clone()
+					})
+					.collect();
+
+				// Apps which couldn't be queried can't be shown to meet their constraints
+				if let Some(err) = &shared.error {
+					apps.extend(self.unqueried(container, &apps, err));
+				}
+
+				container::Status {
+					name: name.to_string(),
+					platform: group.settings.platform.clone(),
+					apps,
+					..shared.clone()
+				}
+			})
+			.collect()
+	}
+
+	/// Failed checks for a container's constrained apps which have no status
+	/// because the container itself couldn't be queried
+	fn unqueried(
+		&self,
+		container: &container::Container,
+		queried: &[application::Status],
+		err: &str,
+	) -> Vec<application::Status> {
+		container
+			.apps
+			.iter()
+			.filter(|x| !queried.iter().any(|y| &y.name == *x))
+			.filter(|x| self.constraints(container, x).check(None).is_some())
+			.map(|x| {
+				let failure = application::Failure::new(application::FailureKind::Execution)
+					.with_message(err.to_string());
+				application::Status {
+					check: Some(application::Check::Fail {
+						reason: format!("container could not be queried: {err}"),
+					}),
+					..application::Status::failed(x.clone(), failure)
+				}
+			})
+			.collect()
+	}
+
+	/// An app's version constraints, or the container's for it instead
+	fn constraints(
+		&self,
+		container: &container::Container,
+		app_name: &str,
+	) -> application::Constraints {
+		match container.constraints.get(app_name) {
+			Some(x) => x.clone(),
+			None => self
+				.applications
+				.get(app_name)
+				.map(|x| x.constraints.clone())
+				.unwrap_or_default(),
+		}
+	}
+
 	fn container_status(
 		&self,
 		name: &String,
-		container: &container::Container,
+		mut apps: Vec<String>,
+		settings: &runtime::Settings,
+		clean: bool,
+		pulls: Option<&runtime::Semaphore>,
+		instance: &dyn ContainerRuntime,
 	) -> container::Status {
 		let mut container_status = container::Status::new(name.clone());
-		let mut apps = container.apps.clone();
 		apps.sort();
+		apps.dedup();
+
+		let instance = runtime::Retrying::new(instance, self.options.retries);
+		let permit = pulls.map(runtime::Semaphore::acquire);
+		let pulled = instance.pull();
+		drop(permit);
+		container_status.attempts = instance.take_attempts();
 
-		let instance = docker::Docker::new(name, &container.path);
+		if let Err(err) = pulled {
+			eprintln!("Unable to start container {}: {err}", &name);
+			container_status.error = Some(err.to_string());
+			return container_status;
+		}
 
-		instance.run().expect("Unable to start docker container");
+		// A failed run may still have created the container, so guard it too
+		let guard = runtime::Guard::new(&instance);
+		if let Err(err) = instance.run() {
+			eprintln!("Unable to start container {}: {err}", &name);
+			container_status.error = Some(err.to_string());
+			return container_status;
+		}
+
+		// Missing metadata is worth a warning, but shouldn't stop the query
+		// unless the image is pinned, since then it can't be verified
+		let verified = match instance.inspect() {
+			Ok(x) => {
+				let result = match &settings.digest {
+					Some(expected) if !x.digests.contains(expected) => {
+						Err(docker::DockerError::DigestMismatch {
+							expected: expected.clone(),
+							actual: x.digest.clone(),
+						})
+					}
+					_ => Ok(()),
+				};
+				container_status.image = Some(x);
+				result
+			}
+			Err(err) if settings.digest.is_some() => Err(err),
+			Err(err) => {
+				eprintln!("Unable to inspect image for container {}: {err}", &name);
+				Ok(())
+			}
+		};
+
+		if let Err(err) = verified {
+			eprintln!("Unable to verify image for container {}: {err}", &name);
+			container_status.error = Some(err.to_string());
+			return container_status;
+		}
+
+		let entrypoint = match &container_status.image {
+			Some(x) => x.entrypoint.as_slice(),
+			None => &[],
+		};
+		let prefix = settings.command_prefix(entrypoint);
 
 		for app_name in apps {
-			let app = match self.applications.get(&app_name) {
-				Some(app) => app,
-				None => {
-					eprintln!(
-						"Config error for {} on {}: App is not defined",
-						&app_name, &name
-					);
-					eprintln!(
-						"-- hint: If you're sure you have a config for {}, look for typos.",
-						&app_name
-					);
-					continue;
-				}
-			};
-			let output = instance.execute(&app.version_command);
-
-			match app.query_version(&output) {
-				Ok(version) => {
-					let eol_status: Option<String> = match &app.eol {
-						Some(x) => match x.query(&version) {
-							Ok(cycle) => Some(cycle.into()),
-							_ => None,
-						},
-						_ => None,
-					};
+			let mut app_status = self.app_status(app_name, settings, &prefix, &instance);
+			app_status.attempts = instance.take_attempts();
+			container_status.apps.push(app_status);
+		}
 
-					container_status.apps.push(application::Status {
-						name: app_name,
-						version,
-						eol_status,
-					});
+		if settings.inventory {
+			let timeout = settings.timeouts.exec();
+			let found = package::inventory(&instance, settings.probe, &prefix, timeout)
+				.and_then(|x| Ok((x, package::distro(&instance, timeout)?)));
+			match found {
+				Ok((packages, distro)) => {
+					container_status.inventory = Some(packages);
+					container_status.distro = distro;
 				}
-				_ => {
-					eprintln!("Error querying app version for {} on {}", &app_name, &name);
-					eprintln!("-- hint: Your version command was: {}", app.version_command);
-					eprintln!(
-						"         Your regex query was: {}",
-						app.version_regex.as_str()
-					);
-					eprintln!("         Your regex input was: {output}");
+				Err(err) => {
+					eprintln!("Unable to take inventory of container {}: {err}", &name);
+					container_status.error = Some(err.to_string());
 				}
 			}
+			instance.take_attempts();
 		}
 
-		instance
-			.stop(self.options.clean_after_query)
-			.expect("Unable to clean up docker container");
+		let cleanup = guard.stop().and_then(|_| match clean {
+			true => instance.clean(),
+			false => Ok(()),
+		});
+
+		if let Err(err) = cleanup {
+			eprintln!("Unable to clean up container {}: {err}", &name);
+			container_status.error = Some(err.to_string());
+		}
 
 		container_status
 	}
+
+	fn app_status(
+		&self,
+		app_name: String,
+		settings: &runtime::Settings,
+		prefix: &[String],
+		instance: &dyn ContainerRuntime,
+	) -> application::Status {
+		let app = match self.applications.get(&app_name) {
+			Some(app) => app,
+			None => {
+				let failure = application::Failure::new(application::FailureKind::Undefined);
+				return application::Status::failed(app_name, failure);
+			}
+		};
+
+		let input = match Self::probe_app(app, settings, prefix, instance) {
+			Ok(x) => x,
+			Err(failure) => return application::Status::failed(app_name, *failure),
+		};
+
+		match app.query_version(&input) {
+			Ok(version) => {
+				let eol_status: Option<String> = match &app.eol {
+					Some(x) => match x.query(&version) {
+						Ok(cycle) => Some(cycle.into()),
+						_ => None,
+					},
+					_ => None,
+				};
+
+				application::Status::new(app_name, version, eol_status)
+			}
+			_ => {
+				let failure = app
+					.failure(application::FailureKind::NoMatch)
+					.with_output(input);
+				application::Status::failed(app_name, failure)
+			}
+		}
+	}
+
+	/// Get the text to search for an app's version, from a file or a command
+	///
+	/// Commands are run after `prefix`, e.g., the image's entrypoint.
+	fn probe_app(
+		app: &application::Application,
+		settings: &runtime::Settings,
+		prefix: &[String],
+		instance: &dyn ContainerRuntime,
+	) -> Result<String, Box<application::Failure>> {
+		let execution_failure = |err: &dyn Error| {
+			Box::new(
+				app.failure(application::FailureKind::Execution)
+					.with_message(err.to_string()),
+			)
+		};
+		let runtime_failure = |err: docker::DockerError| match err {
+			docker::DockerError::Timeout(_) => Box::new(
+				app.failure(application::FailureKind::Timeout)
+					.with_message(err.to_string()),
+			),
+			_ => execution_failure(&err),
+		};
+		let timeout = app
+			.timeout
+			.map(Duration::from_secs)
+			.or(settings.timeouts.exec());
+
+		if let Some(file) = &app.version_file {
+			let contents = instance.read_file(file, timeout).map_err(runtime_failure)?;
+
+			return String::from_utf8(contents).map_err(|err| execution_failure(&err));
+		}
+
+		if let Some(name) = &app.package {
+			return package::query(instance, name, settings.probe, prefix, timeout).map_err(
+				|err| match err {
+					package::Error::NoManager => {
+						Box::new(app.failure(application::FailureKind::NoPackageManager))
+					}
+					package::Error::NotInstalled(manager) => Box::new(
+						app.failure(application::FailureKind::NotInstalled)
+							.with_message(format!(
+								"{name} is not installed according to {manager}"
+							)),
+					),
+					package::Error::Runtime(err) => runtime_failure(err),
+				},
+			);
+		}
+
+		let command = match (&app.version_command, settings.probe) {
+			(None, _) => return Err(Box::new(app.failure(application::FailureKind::NoProbe))),
+			(Some(_), runtime::Probe::Files) => {
+				return Err(Box::new(app.failure(application::FailureKind::NoShell)))
+			}
+			(Some(x), runtime::Probe::Exec) => x,
+		};
+
+		let output = instance
+			.execute(&[prefix, &command.argv()].concat(), timeout)
+			.map_err(runtime_failure)?;
+		let stream = output.stream(app.version_stream);
+
+		match output.success() {
+			true => Ok(stream),
+			false => Err(Box::new(
+				app.failure(application::FailureKind::NonZeroExit)
+					.with_exit_code(output.status)
+					.with_output(stream),
+			)),
+		}
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use crate::{
-		container::Container, ApplicationMap, Config, ContainerMap, FilterFunction, Options,
+		application::{Application, Check, Constraints, FailureKind, VersionCommand},
+		container::{self, Container},
+		docker::DockerError,
+		runtime::{
+			self, ContainerRuntime, ExecOutput, Probe, PullPolicy, Retries, Stream, Timeouts,
+		},
+		ApplicationMap, Config, ContainerMap, FilterFunction, Options,
+	};
+	use std::{
+		cell::{Cell, RefCell},
+		num::NonZeroUsize,
+		sync::atomic::{AtomicUsize, Ordering},
+		time::Duration,
 	};
 
+	/// Query a container with its resolved settings
+	fn query(config: &Config, container: &Container, runtime: &FakeRuntime) -> container::Status {
+		let settings = config.settings(container, None);
+		let apps = container.apps.clone();
+		let clean = config.options.clean_after_query;
+		config.container_status(&String::from("test"), apps, &settings, clean, None, runtime)
+	}
+
+	/// A runtime double which records lifecycle calls and echoes canned output
+	#[derive(Default)]
+	struct FakeRuntime {
+		calls: RefCell<Vec<String>>,
+		missing_image: bool,
+		failed_run: bool,
+		/// How many more pulls should fail with a registry error
+		flaky_pulls: Cell<u32>,
+		/// How many more execs should fail with a daemon error
+		flaky_execs: Cell<u32>,
+		/// Whether dpkg keeps a status file per package, as distroless does
+		distroless: bool,
+		/// Whether packages are only known to rpm, which has to be run
+		rpm: bool,
+		/// Whether reading files fails in the runtime
+		unreadable: bool,
+	}
+
+	impl ContainerRuntime for FakeRuntime {
+		fn pull(&self) -> Result<(), DockerError> {
+			self.calls.borrow_mut().push(String::from("pull"));
+			if self.flaky_pulls.get() > 0 {
+				self.flaky_pulls.set(self.flaky_pulls.get() - 1);
+				return Err(DockerError::PullFailed {
+					image: String::from("path"),
+					message: String::from("503 Service Unavailable"),
+				});
+			}
+
+			match self.missing_image {
+				true => Err(DockerError::ImageNotFound(String::from("path"))),
+				false => Ok(()),
+			}
+		}
+
+		fn inspect(&self) -> Result<runtime::Image, DockerError> {
+			self.calls.borrow_mut().push(String::from("inspect"));
+			Ok(runtime::Image {
+				digest: Some(String::from("sha256:digest")),
+				digests: vec![String::from("sha256:digest")],
+				id: String::from("sha256:id"),
+				entrypoint: vec![String::from("/entrypoint.sh")],
+				..Default::default()
+			})
+		}
+
+		fn run(&self) -> Result<(), DockerError> {
+			self.calls.borrow_mut().push(String::from("run"));
+			match self.failed_run {
+				true => Err(DockerError::NonZeroExit {
+					status: Some(125),
+					stderr: String::from("name in use"),
+				}),
+				false => Ok(()),
+			}
+		}
+
+		fn execute(
+			&self,
+			args: &[String],
+			timeout: Option<Duration>,
+		) -> Result<ExecOutput, DockerError> {
+			self.calls
+				.borrow_mut()
+				.push(format!("execute {}", args.join(" ")));
+			if self.flaky_execs.get() > 0 {
+				self.flaky_execs.set(self.flaky_execs.get() - 1);
+				return Err(DockerError::NonZeroExit {
+					status: Some(125),
+					stderr: String::from("Error response from daemon: i/o timeout"),
+				});
+			}
+
+			let output = match args.last().map(String::as_str) {
+				Some("sleep infinity") => return Err(DockerError::Timeout(timeout.unwrap())),
+				Some("java -version") => ExecOutput {
+					stderr: String::from("test: 17.0.2"),
+					status: Some(0),
+					..Default::default()
+				},
+				// Several versions of a package may be installed at once
+				Some("kernel") => ExecOutput {
+					stdout: String::from("5.14.0-1.el9\n5.14.0-2.el9\n"),
+					status: Some(0),
+					..Default::default()
+				},
+				Some("false") => ExecOutput {
+					stderr: String::from("oops"),
+					status: Some(1),
+					..Default::default()
+				},
+				_ => ExecOutput {
+					stdout: String::from("test: 1.2.3"),
+					status: Some(0),
+					..Default::default()
+				},
+			};
+
+			Ok(output)
+		}
+
+		fn read_file(
+			&self,
+			path: &str,
+			_timeout: Option<Duration>,
+		) -> Result<Vec<u8>, DockerError> {
+			self.calls.borrow_mut().push(format!("read_file {path}"));
+
+			match path {
+				_ if self.unreadable => Err(DockerError::NonZeroExit {
+					status: Some(1),
+					stderr: String::from("Error response from daemon: permission denied"),
+				}),
+				"/var/lib/dpkg/status" if self.distroless || self.rpm => {
+					Err(DockerError::FileNotFound(String::from(path)))
+				}
+				"/var/lib/dpkg/status" => Ok(
+					b"Package: openssl\nStatus: install ok installed\nVersion: 3.0.2-0ubuntu1\n"
+						.to_vec(),
+				),
+				"/etc/os-release" if !self.rpm => {
+					Ok(b"test: 9.9\nID=ubuntu\nVERSION_ID=\"22.04\"\n".to_vec())
+				}
+				_ if self.rpm => Err(DockerError::FileNotFound(String::from(path))),
+				_ => Ok(b"test: 9.9".to_vec()),
+			}
+		}
+
+		fn read_dir(
+			&self,
+			path: &str,
+			_timeout: Option<Duration>,
+		) -> Result<Vec<Vec<u8>>, DockerError> {
+			self.calls.borrow_mut().push(format!("read_dir {path}"));
+
+			match path {
+				"/var/lib/dpkg/status.d" if self.distroless => Ok(vec![
+					b"Package: libc6\nVersion: 2.36-9\n".to_vec(),
+					b"Package: openssl\nVersion: 3.0.11-1".to_vec(),
+				]),
+				_ => Err(DockerError::FileNotFound(String::from(path))),
+			}
+		}
+
+		fn stop(&self) -> Result<(), DockerError> {
+			self.calls.borrow_mut().push(String::from("stop"));
+			Ok(())
+		}
+
+		fn clean(&self) -> Result<(), DockerError> {
+			self.calls.borrow_mut().push(String::from("clean"));
+			Ok(())
+		}
+	}
+
 	#[test]
 	fn filter_by_any() {
 		let containers = ContainerMap::from([
@@ -277,4 +934,814 @@ mod tests {
 
 		assert_eq!(config.containers.len(), 1);
 	}
+
+	#[test]
+	fn container_status_uses_runtime() {
+		let container = Container::default();
+		let applications = ApplicationMap::from([(
+			String::from("bash"),
+			Application {
+				version_command: Some(VersionCommand::Args(vec![
+					String::from("bash"),
+					String::from("--version"),
+				])),
+				..Default::default()
+			},
+		)]);
+		let options = Options {
+			clean_after_query: true,
+			..Default::default()
+		};
+		let config = Config::new(ContainerMap::new(), applications, options);
+		let runtime = FakeRuntime::default();
+
+		let status = query(&config, &container, &runtime);
+
+		assert_eq!(status.apps.len(), 1);
+		assert_eq!(status.apps[0].version, Some(String::from("1.2.3")));
+		assert_eq!(
+			status.apps[0].parsed_version,
+			"1.2.3".parse::<crate::version::Version>().ok()
+		);
+		assert_eq!(
+			status.image.unwrap().digest,
+			Some(String::from("sha256:digest"))
+		);
+		assert_eq!(
+			runtime.calls.into_inner(),
+			vec![
+				"pull",
+				"run",
+				"inspect",
+				"execute bash --version",
+				"stop",
+				"clean"
+			]
+		);
+	}
+
+	#[test]
+	fn container_status_records_runtime_failure() {
+		let config = Config::default();
+		let runtime = FakeRuntime {
+			missing_image: true,
+			..Default::default()
+		};
+
+		let status = query(&config, &Container::default(), &runtime);
+
+		assert_eq!(status.error, Some(String::from("Image not found: path")));
+		assert!(status.apps.is_empty());
+		assert_eq!(runtime.calls.into_inner(), vec!["pull"]);
+	}
+
+	#[test]
+	fn container_status_retries_transient_failures() {
+		let retries = Retries {
+			count: 2,
+			backoff: 0.0,
+		};
+		let options = Options::default().with_retries(retries);
+		let applications = ApplicationMap::from([(String::from("bash"), Application::default())]);
+		let config = Config::new(ContainerMap::new(), applications, options);
+		let runtime = FakeRuntime {
+			flaky_pulls: Cell::new(2),
+			..Default::default()
+		};
+
+		let status = query(&config, &Container::default(), &runtime);
+
+		assert_eq!(status.error, None);
+		assert_eq!(status.attempts, 3);
+		assert_eq!(status.apps[0].attempts, 1);
+
+		let runtime = FakeRuntime {
+			flaky_pulls: Cell::new(3),
+			..Default::default()
+		};
+
+		let status = query(&config, &Container::default(), &runtime);
+
+		assert!(status.error.is_some());
+		assert_eq!(status.attempts, 3);
+
+		let runtime = FakeRuntime {
+			flaky_execs: Cell::new(2),
+			..Default::default()
+		};
+
+		let status = query(&config, &Container::default(), &runtime);
+
+		assert_eq!(status.apps[0].version, Some(String::from("1.2.3")));
+		assert_eq!(status.apps[0].attempts, 3);
+	}
+
+	#[test]
+	fn unusable_backoffs_do_not_wait() {
+		let runtime = FakeRuntime {
+			flaky_pulls: Cell::new(1),
+			..Default::default()
+		};
+		let retries = Retries {
+			count: 1,
+			backoff: f64::INFINITY,
+		};
+
+		let instance = runtime::Retrying::new(&runtime, retries);
+
+		assert!(instance.pull().is_ok());
+		assert_eq!(instance.take_attempts(), 2);
+	}
+
+	#[test]
+	fn container_status_does_not_retry_permanent_failures() {
+		let options = Options::default().with_retries(Retries {
+			count: 2,
+			backoff: 0.0,
+		});
+		let config = Config::new(ContainerMap::new(), ApplicationMap::new(), options);
+		let runtime = FakeRuntime {
+			missing_image: true,
+			..Default::default()
+		};
+
+		let status = query(&config, &Container::default(), &runtime);
+
+		assert_eq!(status.attempts, 1);
+		assert_eq!(runtime.calls.into_inner(), vec!["pull"]);
+	}
+
+	#[test]
+	fn entries_sharing_an_image_share_a_container() {
+		let entry = |path: &str, apps: &[&str]| Container {
+			path: String::from(path),
+			apps: apps.iter().map(|x| String::from(*x)).collect(),
+			..Default::default()
+		};
+		let containers = ContainerMap::from([
+			(String::from("api"), entry("ubuntu", &["bash", "os"])),
+			(String::from("web"), entry("ubuntu", &["bash", "grep"])),
+			(String::from("db"), entry("postgres", &["bash"])),
+		]);
+		let applications = ApplicationMap::from([
+			(String::from("bash"), Application::default()),
+			(String::from("grep"), Application::default()),
+			(String::from("os"), Application::default()),
+		]);
+		let config = Config::new(containers, applications, Options::default());
+		let runtime = FakeRuntime::default();
+
+		let groups = config.groups();
+		let ubuntu = groups.iter().find(|x| x.path == "ubuntu").unwrap();
+		let statuses = config.group_status(ubuntu, None, &runtime);
+
+		assert_eq!(groups.len(), 2);
+		assert_eq!(statuses.len(), 2);
+		assert_eq!(statuses[0].name, "api");
+		assert_eq!(
+			statuses[0].apps.iter().map(|x| &x.name).collect::<Vec<_>>(),
+			["bash", "os"]
+		);
+		assert_eq!(statuses[1].name, "web");
+		assert_eq!(
+			statuses[1].apps.iter().map(|x| &x.name).collect::<Vec<_>>(),
+			["bash", "grep"]
+		);
+		assert_eq!(
+			runtime.calls.into_inner(),
+			vec![
+				"pull",
+				"run",
+				"inspect",
+				"execute sh -c ",
+				"execute sh -c ",
+				"execute sh -c ",
+				"stop"
+			]
+		);
+	}
+
+	#[test]
+	fn only_the_last_group_using_an_image_removes_it() {
+		let entry = |path: &str, platforms: &[&str]| Container {
+			path: String::from(path),
+			platforms: Some(platforms.iter().map(|x| String::from(*x)).collect()),
+			..Default::default()
+		};
+		let containers = ContainerMap::from([
+			(
+				String::from("ubuntu"),
+				entry("ubuntu", &["linux/amd64", "linux/arm64"]),
+			),
+			(String::from("postgres"), entry("postgres", &[])),
+		]);
+		let options = Options {
+			clean_after_query: true,
+			..Default::default()
+		};
+		let config = Config::new(containers, ApplicationMap::new(), options);
+
+		let groups = config.groups();
+		let cleans = |path: &str| {
+			groups
+				.iter()
+				.filter(|x| x.path == path)
+				.map(|x| x.clean)
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(cleans("ubuntu"), [false, true]);
+		assert_eq!(cleans("postgres"), [true]);
+	}
+
+	#[test]
+	fn container_status_verifies_pinned_digests() {
+		let pinned = |path: &str, digest: Option<&str>| Container {
+			path: String::from(path),
+			digest: digest.map(String::from),
+			..Default::default()
+		};
+		let config = Config::default();
+
+		let runtime = FakeRuntime::default();
+		let status = query(&config, &pinned("ubuntu@sha256:digest", None), &runtime);
+		assert_eq!(status.error, None);
+
+		let runtime = FakeRuntime::default();
+		let status = query(&config, &pinned("ubuntu", Some("sha256:other")), &runtime);
+		assert_eq!(
+			status.error.as_deref(),
+			Some("Image digest sha256:digest does not match expected sha256:other")
+		);
+		assert_eq!(
+			runtime.calls.into_inner(),
+			vec!["pull", "run", "inspect", "stop"]
+		);
+	}
+
+	#[test]
+	fn container_status_runs_commands_through_entrypoint() {
+		let container = Container {
+			keep_entrypoint: true,
+			init: Some(String::from(". /opt/env.sh")),
+			..Default::default()
+		};
+		let applications = ApplicationMap::from([(
+			String::from("bash"),
+			Application {
+				version_command: Some(VersionCommand::from("bash --version")),
+				..Default::default()
+			},
+		)]);
+		let config = Config::new(ContainerMap::new(), applications, Options::default());
+		let runtime = FakeRuntime::default();
+
+		let status = query(&config, &container, &runtime);
+
+		assert_eq!(status.apps[0].version, Some(String::from("1.2.3")));
+		assert_eq!(
+			runtime.calls.into_inner()[3],
+			"execute /entrypoint.sh sh -c . /opt/env.sh\nexec \"$@\" sh sh -c bash --version"
+		);
+	}
+
+	#[test]
+	fn container_status_removes_failed_containers() {
+		let config = Config::default();
+		let runtime = FakeRuntime {
+			failed_run: true,
+			..Default::default()
+		};
+
+		let status = query(&config, &Container::default(), &runtime);
+
+		assert!(status.error.is_some());
+		assert_eq!(runtime.calls.into_inner(), vec!["pull", "run", "stop"]);
+	}
+
+	#[test]
+	fn guard_stops_containers_on_panic() {
+		let runtime = FakeRuntime::default();
+
+		let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+			let _guard = runtime::Guard::new(&runtime);
+			panic!("probe failed");
+		}));
+
+		assert!(result.is_err());
+		assert_eq!(runtime.calls.into_inner(), vec!["stop"]);
+	}
+
+	#[test]
+	fn container_status_reports_app_failures() {
+		let container = Container {
+			apps: vec![String::from("missing"), String::from("nomatch")],
+			..Default::default()
+		};
+		let applications = ApplicationMap::from([(
+			String::from("nomatch"),
+			Application {
+				version_regex: regex::Regex::new(r"other: (?P<version>[0-9.]+)").unwrap(),
+				version_command: Some(VersionCommand::from("nomatch --version")),
+				..Default::default()
+			},
+		)]);
+		let config = Config::new(ContainerMap::new(), applications, Options::default());
+
+		let status = query(&config, &container, &FakeRuntime::default());
+
+		assert_eq!(status.apps.len(), 2);
+		assert_eq!(status.apps[0].version, None);
+		let failure = status.apps[0].failure.as_ref().unwrap();
+		assert_eq!(failure.kind, FailureKind::Undefined);
+
+		let failure = status.apps[1].failure.as_ref().unwrap();
+		assert_eq!(failure.kind, FailureKind::NoMatch);
+		assert_eq!(failure.command.as_deref(), Some("nomatch --version"));
+		assert_eq!(failure.output.as_deref(), Some("test: 1.2.3"));
+		assert_eq!(
+			failure.regex.as_deref(),
+			Some(r"other: (?P<version>[0-9.]+)")
+		);
+	}
+
+	#[test]
+	fn version_command_forms() {
+		let applications: ApplicationMap = toml::from_str(
+			r#"
+			[bash]
+			version_regex = "(?P<version>.+)"
+			version_command = ["bash", "--version"]
+
+			[openssl]
+			version_regex = "(?P<version>.+)"
+			version_command = "dpkg -s openssl | grep Version"
+			"#,
+		)
+		.unwrap();
+
+		assert_eq!(
+			applications["bash"]
+				.version_command
+				.as_ref()
+				.unwrap()
+				.argv(),
+			["bash", "--version"]
+		);
+		assert_eq!(
+			applications["openssl"]
+				.version_command
+				.as_ref()
+				.unwrap()
+				.argv(),
+			["sh", "-c", "dpkg -s openssl | grep Version"]
+		);
+	}
+
+	#[test]
+	fn container_status_reads_streams_and_exit_codes() {
+		let container = Container {
+			apps: vec![String::from("failing"), String::from("java")],
+			..Default::default()
+		};
+		let applications = ApplicationMap::from([
+			(
+				String::from("java"),
+				Application {
+					version_command: Some(VersionCommand::from("java -version")),
+					version_stream: Stream::Stderr,
+					..Default::default()
+				},
+			),
+			(
+				String::from("failing"),
+				Application {
+					version_command: Some(VersionCommand::from("false")),
+					version_stream: Stream::Both,
+					..Default::default()
+				},
+			),
+		]);
+		let config = Config::new(ContainerMap::new(), applications, Options::default());
+
+		let status = query(&config, &container, &FakeRuntime::default());
+
+		let failure = status.apps[0].failure.as_ref().unwrap();
+		assert_eq!(failure.kind, FailureKind::NonZeroExit);
+		assert_eq!(failure.exit_code, Some(1));
+		assert_eq!(failure.output.as_deref(), Some("oops"));
+		assert_eq!(status.apps[1].version, Some(String::from("17.0.2")));
+	}
+
+	#[test]
+	fn container_pull_policy_overrides_options() {
+		let container = Container {
+			pull_policy: Some(PullPolicy::Never),
+			..Default::default()
+		};
+		let options = Options::default().with_pull_policy(PullPolicy::IfMissing);
+		let config = Config::new(ContainerMap::new(), ApplicationMap::new(), options);
+
+		assert_eq!(
+			config.settings(&container, None).pull_policy,
+			PullPolicy::Never
+		);
+		assert_eq!(
+			config.settings(&Container::default(), None).pull_policy,
+			PullPolicy::IfMissing
+		);
+	}
+
+	#[test]
+	fn container_status_reports_timeouts() {
+		let container = Container {
+			apps: vec![String::from("prompt")],
+			timeouts: Timeouts {
+				exec: Some(5),
+				..Default::default()
+			},
+			..Default::default()
+		};
+		let applications = ApplicationMap::from([(
+			String::from("prompt"),
+			Application {
+				version_command: Some(VersionCommand::from("sleep infinity")),
+				timeout: Some(2),
+				..Default::default()
+			},
+		)]);
+		let options = Options::default().with_timeouts(Timeouts {
+			exec: Some(60),
+			pull: Some(600),
+			..Default::default()
+		});
+		let config = Config::new(ContainerMap::new(), applications, options);
+
+		let status = query(&config, &container, &FakeRuntime::default());
+
+		let failure = status.apps[0].failure.as_ref().unwrap();
+		assert_eq!(failure.kind, FailureKind::Timeout);
+		assert_eq!(failure.message.as_deref(), Some("Timed out after 2s"));
+		assert_eq!(
+			config.settings(&container, None).timeouts,
+			Timeouts {
+				pull: Some(600),
+				start: None,
+				exec: Some(5)
+			}
+		);
+	}
+
+	#[test]
+	fn container_status_probes_files_without_a_shell() {
+		let container = Container {
+			apps: vec![String::from("bash"), String::from("os")],
+			probe: Probe::Files,
+			..Default::default()
+		};
+		let applications = ApplicationMap::from([
+			(String::from("bash"), Application::default()),
+			(
+				String::from("os"),
+				Application {
+					version_command: None,
+					version_file: Some(String::from("/etc/os-release")),
+					..Default::default()
+				},
+			),
+		]);
+		let config = Config::new(ContainerMap::new(), applications, Options::default());
+		let runtime = FakeRuntime::default();
+
+		let status = query(&config, &container, &runtime);
+
+		let failure = status.apps[0].failure.as_ref().unwrap();
+		assert_eq!(failure.kind, FailureKind::NoShell);
+		assert_eq!(status.apps[1].version, Some(String::from("9.9")));
+		assert_eq!(
+			runtime.calls.into_inner(),
+			vec![
+				"pull",
+				"run",
+				"inspect",
+				"read_file /etc/os-release",
+				"stop"
+			]
+		);
+	}
+
+	#[test]
+	fn container_status_reads_package_versions() {
+		let container = Container {
+			apps: vec![String::from("curl"), String::from("openssl")],
+			probe: Probe::Files,
+			..Default::default()
+		};
+		let package = |name: &str| Application {
+			version_regex: regex::Regex::new(r"(?P<version>.+)").unwrap(),
+			version_command: None,
+			package: Some(String::from(name)),
+			..Default::default()
+		};
+		let applications = ApplicationMap::from([
+			(String::from("openssl"), package("openssl")),
+			(String::from("curl"), package("curl")),
+		]);
+		let config = Config::new(ContainerMap::new(), applications, Options::default());
+
+		let status = query(&config, &container, &FakeRuntime::default());
+
+		assert_eq!(status.apps[1].version.as_deref(), Some("3.0.2-0ubuntu1"));
+		let failure = status.apps[0].failure.as_ref().unwrap();
+		assert_eq!(failure.kind, FailureKind::NotInstalled);
+		assert_eq!(failure.to_string(), "package `curl` is not installed");
+		assert_eq!(failure.command, None);
+		assert_eq!(
+			failure.message.as_deref(),
+			Some("curl is not installed according to dpkg")
+		);
+	}
+
+	#[test]
+	fn container_status_reads_distroless_package_versions() {
+		let container = Container {
+			apps: vec![String::from("curl"), String::from("openssl")],
+			probe: Probe::Files,
+			..Default::default()
+		};
+		let package = |name: &str| Application {
+			version_regex: regex::Regex::new(r"(?P<version>.+)").unwrap(),
+			version_command: None,
+			package: Some(String::from(name)),
+			..Default::default()
+		};
+		let applications = ApplicationMap::from([
+			(String::from("openssl"), package("openssl")),
+			(String::from("curl"), package("curl")),
+		]);
+		let config = Config::new(ContainerMap::new(), applications, Options::default());
+		let runtime = FakeRuntime {
+			distroless: true,
+			..Default::default()
+		};
+
+		let status = query(&config, &container, &runtime);
+
+		assert_eq!(status.apps[1].version.as_deref(), Some("3.0.11-1"));
+		let failure = status.apps[0].failure.as_ref().unwrap();
+		assert_eq!(
+			failure.message.as_deref(),
+			Some("curl is not installed according to dpkg")
+		);
+	}
+
+	#[test]
+	fn container_status_reports_unreadable_packages() {
+		let container = Container {
+			apps: vec![String::from("kernel")],
+			..Default::default()
+		};
+		let applications = ApplicationMap::from([(
+			String::from("kernel"),
+			Application {
+				version_regex: regex::Regex::new(r"(?P<version>.+)").unwrap(),
+				version_command: None,
+				package: Some(String::from("kernel")),
+				..Default::default()
+			},
+		)]);
+		let config = Config::new(ContainerMap::new(), applications, Options::default());
+
+		let runtime = FakeRuntime {
+			rpm: true,
+			..Default::default()
+		};
+		let status = query(&config, &container, &runtime);
+		assert_eq!(status.apps[0].version.as_deref(), Some("5.14.0-1.el9"));
+		// Looking through each package database first isn't a retry
+		assert_eq!(status.apps[0].attempts, 1);
+
+		// Only a missing database means the package manager isn't there
+		let runtime = FakeRuntime {
+			unreadable: true,
+			..Default::default()
+		};
+		let status = query(&config, &container, &runtime);
+		let failure = status.apps[0].failure.as_ref().unwrap();
+		assert_eq!(failure.kind, FailureKind::Execution);
+		assert!(!runtime
+			.calls
+			.into_inner()
+			.iter()
+			.any(|x| x.starts_with("execute")));
+	}
+
+	#[test]
+	fn container_status_takes_inventory() {
+		let container = Container {
+			apps: vec![],
+			probe: Probe::Files,
+			inventory: Some(true),
+			..Default::default()
+		};
+		let config = Config::default();
+		let runtime = FakeRuntime::default();
+
+		let status = query(&config, &container, &runtime);
+
+		let inventory = status.inventory.unwrap();
+		assert_eq!(inventory.len(), 1);
+		assert_eq!(inventory[0].name, "openssl");
+		assert_eq!(inventory[0].manager, crate::package::Manager::Dpkg);
+		assert_eq!(status.distro.unwrap().id, "ubuntu");
+		assert_eq!(
+			query(&config, &Container::default(), &runtime).inventory,
+			None
+		);
+	}
+
+	#[test]
+	fn container_status_takes_distroless_inventory() {
+		let container = Container {
+			probe: Probe::Files,
+			inventory: Some(true),
+			..Default::default()
+		};
+		let runtime = FakeRuntime {
+			distroless: true,
+			..Default::default()
+		};
+
+		let status = query(&Config::default(), &container, &runtime);
+
+		let inventory = status.inventory.unwrap();
+		assert_eq!(
+			inventory
+				.iter()
+				.map(|x| (x.name.as_str(), x.version.as_str()))
+				.collect::<Vec<_>>(),
+			[("libc6", "2.36-9"), ("openssl", "3.0.11-1")]
+		);
+	}
+
+	#[test]
+	fn container_constraints_override_apps() {
+		let entry = |name: &str, constraints: &[(&str, &str)]| {
+			let container = Container {
+				path: String::from("ubuntu"),
+				apps: vec![String::from("bash")],
+				constraints: constraints
+					.iter()
+					.map(|(app, allowed)| {
+						let constraints = Constraints {
+							allowed: allowed.parse().ok(),
+							..Default::default()
+						};
+						(String::from(*app), constraints)
+					})
+					.collect(),
+				..Default::default()
+			};
+			(String::from(name), container)
+		};
+		let containers = ContainerMap::from([
+			entry("strict", &[("bash", ">=2")]),
+			entry("lenient", &[("bash", ">=1, <2")]),
+			entry("default", &[]),
+		]);
+		let applications: ApplicationMap = toml::from_str(
+			r#"
+			[bash]
+			version_regex = "test: (?P<version>[0-9.]+)"
+			version_command = "bash --version"
+			minimum = "1.3"
+			"#,
+		)
+		.unwrap();
+		let config = Config::new(containers, applications, Options::default());
+
+		let groups = config.groups();
+		let statuses = config.group_status(&groups[0], None, &FakeRuntime::default());
+		let check = |name: &str| {
+			let status = statuses.iter().find(|x| x.name == name).unwrap();
+			status.apps[0].check.clone().unwrap()
+		};
+
+		assert_eq!(groups.len(), 1);
+		assert_eq!(
+			check("strict"),
+			Check::Fail {
+				reason: String::from("1.2.3 is not allowed by \">=2\"")
+			}
+		);
+		assert_eq!(check("lenient"), Check::Pass);
+		assert_eq!(
+			check("default"),
+			Check::Fail {
+				reason: String::from("1.2.3 is below minimum 1.3")
+			}
+		);
+	}
+
+	#[test]
+	fn unqueried_apps_fail_their_constraints() {
+		let container = Container {
+			path: String::from("ubuntu"),
+			apps: vec![String::from("bash"), String::from("grep")],
+			..Default::default()
+		};
+		let applications: ApplicationMap = toml::from_str(
+			r#"
+			[bash]
+			version_regex = "test: (?P<version>[0-9.]+)"
+			version_command = "bash --version"
+			minimum = "1.0"
+
+			[grep]
+			version_regex = "test: (?P<version>[0-9.]+)"
+			version_command = "grep --version"
+			"#,
+		)
+		.unwrap();
+		let containers = ContainerMap::from([(String::from("ubuntu"), container)]);
+		let config = Config::new(containers, applications, Options::default());
+		let runtime = FakeRuntime {
+			missing_image: true,
+			..Default::default()
+		};
+
+		let groups = config.groups();
+		let statuses = config.group_status(&groups[0], None, &runtime);
+
+		assert_eq!(statuses[0].apps.len(), 1);
+		assert_eq!(statuses[0].apps[0].name, "bash");
+		assert_eq!(
+			statuses[0].apps[0].check,
+			Some(Check::Fail {
+				reason: String::from("container could not be queried: Image not found: path")
+			})
+		);
+	}
+
+	#[test]
+	fn semaphore_limits_concurrency() {
+		let semaphore = runtime::Semaphore::new(2);
+		let active = AtomicUsize::new(0);
+		let most = AtomicUsize::new(0);
+
+		std::thread::scope(|scope| {
+			for _ in 0..8 {
+				scope.spawn(|| {
+					let _permit = semaphore.acquire();
+					let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+					most.fetch_max(now, Ordering::SeqCst);
+					std::thread::sleep(Duration::from_millis(10));
+					active.fetch_sub(1, Ordering::SeqCst);
+				});
+			}
+		});
+
+		assert_eq!(most.into_inner(), 2);
+	}
+
+	#[test]
+	fn run_within_max_parallel() {
+		let containers = ContainerMap::from([
+			(
+				String::from("one"),
+				Container {
+					path: String::from("oci:/nonexistent/one"),
+					..Default::default()
+				},
+			),
+			(
+				String::from("two"),
+				Container {
+					path: String::from("oci:/nonexistent/two"),
+					..Default::default()
+				},
+			),
+		]);
+		let options =
+			Options::default().with_max_parallel(NonZeroUsize::new(1), NonZeroUsize::new(1));
+		let config = Config::new(containers, ApplicationMap::new(), options);
+
+		let statuses = config.run().unwrap();
+
+		assert_eq!(statuses.len(), 2);
+		assert!(statuses.iter().all(|x| x.error.is_some()));
+	}
+
+	#[test]
+	fn archives_are_probed_by_files() {
+		let container = Container {
+			path: String::from("docker-archive:image.tar"),
+			..Default::default()
+		};
+
+		assert_eq!(
+			Config::default().settings(&container, None).probe,
+			Probe::Files
+		);
+	}
 }
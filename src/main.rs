@@ -1,13 +1,16 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use core::panic;
 use corrator::{ApplicationMap, Config, ContainerMap, Options};
 use directories::ProjectDirs;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::{fmt::Write, fs, path::Path, process::exit};
+use std::{fmt::Write, fs, num::NonZeroUsize, path::Path, process::exit};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+	#[command(subcommand)]
+	command: Option<Command>,
+
 	/// Specify a directory to load toml files from
 	#[arg(short = 'd', long, default_value_t = default_config_path(), conflicts_with = "config_url", help_heading="Config Settings")]
 	config_directory: String,
@@ -34,7 +37,8 @@ struct Args {
 	)]
 	validate_config_url: bool,
 
-	#[arg(short, long, default_value = "text", value_parser = ["text", "json"], help_heading = "Output")]
+	/// Output format; "cyclonedx-json" and "spdx-json" produce SBOMs
+	#[arg(short, long, default_value = "text", value_parser = ["text", "json", "cyclonedx-json", "spdx-json"], help_heading = "Output")]
 	format: String,
 
 	/// Writes output to a file at this given path if provided
@@ -47,6 +51,50 @@ struct Args {
 	#[arg(long)]
 	clean: bool,
 
+	/// Container engine used to run containers; overrides the config, defaults to docker
+	#[arg(long, value_enum, help_heading = "Runtime")]
+	runtime: Option<corrator::Runtime>,
+
+	/// When to pull images; containers may override this
+	#[arg(long = "pull", value_enum, default_value_t = corrator::runtime::PullPolicy::Always, help_heading = "Runtime")]
+	pull_policy: corrator::runtime::PullPolicy,
+
+	/// Seconds to allow for pulling each image; containers may override this
+	#[arg(long, value_parser = clap::value_parser!(u64).range(1..), help_heading = "Timeouts")]
+	pull_timeout: Option<u64>,
+
+	/// Seconds to allow for starting each container; containers may override this
+	#[arg(long, value_parser = clap::value_parser!(u64).range(1..), help_heading = "Timeouts")]
+	start_timeout: Option<u64>,
+
+	/// Seconds to allow for each version command; containers and apps may override this
+	#[arg(long, value_parser = clap::value_parser!(u64).range(1..), help_heading = "Timeouts")]
+	exec_timeout: Option<u64>,
+
+	/// How many times to retry pulls and version commands which fail transiently
+	#[arg(long, default_value_t = 0, help_heading = "Runtime")]
+	retries: u32,
+
+	/// Seconds to wait before the first retry, doubling for each one after
+	#[arg(long, default_value_t = 1.0, value_parser = parse_backoff, help_heading = "Runtime")]
+	retry_backoff: f64,
+
+	/// How many containers to query at once; defaults to one per CPU
+	#[arg(short = 'j', long, help_heading = "Runtime")]
+	max_parallel: Option<NonZeroUsize>,
+
+	/// How many images to pull at once; defaults to --max-parallel
+	#[arg(long, help_heading = "Runtime")]
+	max_parallel_pulls: Option<NonZeroUsize>,
+
+	/// Alternative podman storage root, e.g., for rootless hosts; overrides the config
+	#[arg(long, help_heading = "Runtime")]
+	podman_root: Option<String>,
+
+	/// List every installed OS and language package; containers may override this
+	#[arg(long, help_heading = "Output")]
+	inventory: bool,
+
 	/// Filter containers by tag; can be used multiple times
 	#[arg(short, long, help_heading = "Filtering")]
 	tag: Option<Vec<String>>,
@@ -64,14 +112,48 @@ struct Args {
 	keep_eol_cache: bool,
 }
 
-impl From<&Args> for Options {
-	fn from(args: &Args) -> Self {
-		Self::new(
-			args.clean,
-			args.tag.clone(),
-			args.name.clone(),
-			args.filter.clone(),
+#[derive(Subcommand, Debug)]
+enum Command {
+	/// Remove containers left behind by interrupted or crashed runs
+	Cleanup,
+}
+
+/// Settings which may be given in the config, rather than on the command line
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+struct Settings {
+	runtime: Option<corrator::Runtime>,
+	podman_root: Option<String>,
+}
+
+impl Args {
+	/// Options from the command line, falling back to the config's settings
+	fn options(&self, settings: Settings) -> Options {
+		Options::new(
+			self.clean,
+			self.tag.clone(),
+			self.name.clone(),
+			self.filter.clone(),
+		)
+		.with_runtime(
+			self.runtime
+				.clone()
+				.or(settings.runtime)
+				.unwrap_or_default(),
 		)
+		.with_podman_root(self.podman_root.clone().or(settings.podman_root))
+		.with_pull_policy(self.pull_policy)
+		.with_max_parallel(self.max_parallel, self.max_parallel_pulls)
+		.with_inventory(self.inventory)
+		.with_retries(corrator::runtime::Retries {
+			count: self.retries,
+			backoff: self.retry_backoff,
+		})
+		.with_timeouts(corrator::runtime::Timeouts {
+			pull: self.pull_timeout,
+			start: self.start_timeout,
+			exec: self.exec_timeout,
+		})
 	}
 }
 
@@ -79,6 +161,19 @@ impl From<&Args> for Options {
 struct JsonConfig {
 	containers: ContainerMap,
 	applications: ApplicationMap,
+	#[serde(flatten)]
+	settings: Settings,
+}
+
+fn parse_backoff(value: &str) -> Result<f64, String> {
+	let backoff: f64 = value
+		.parse()
+		.map_err(|_| format!("{value} is not a number"))?;
+
+	match std::time::Duration::try_from_secs_f64(backoff) {
+		Ok(_) => Ok(backoff),
+		Err(_) => Err(format!("{value} is not a number of seconds")),
+	}
 }
 
 fn default_config_path() -> String {
@@ -92,9 +187,24 @@ fn default_config_path() -> String {
 
 fn main() {
 	let args = Args::parse();
-	let options = Options::from(&args);
 
-	let config = match &args.config_url {
+	if let Some(Command::Cleanup) = args.command {
+		let settings = match &args.config_url {
+			Some(x) => parse_config_url::<JsonConfig>(x).settings,
+			None => parse_settings_file(Path::new(&args.config_directory)),
+		};
+
+		match corrator::cleanup(&args.options(settings), None) {
+			Ok(names) => names.iter().for_each(|x| println!("Removed {x}")),
+			Err(err) => {
+				eprintln!("Unable to clean up containers: {err}");
+				exit(1);
+			}
+		}
+		return;
+	}
+
+	let (containers, applications, settings) = match &args.config_url {
 		Some(x) => {
 			let schema = include_str!("config.schema.json");
 			let schema = serde_json::from_str(schema).expect("Could not read json schema!");
@@ -115,35 +225,71 @@ fn main() {
 			}
 
 			let json_config: JsonConfig = parse_config_url(x);
-			Config::new(json_config.containers, json_config.applications, options)
+			(
+				json_config.containers,
+				json_config.applications,
+				json_config.settings,
+			)
 		}
 
 		None => {
 			let directory = Path::new(&args.config_directory);
-			Config::new(
+			(
 				parse_config_file(directory, "containers.toml"),
 				parse_config_file(directory, "applications.toml"),
-				options,
+				parse_settings_file(directory),
 			)
 		}
 	};
+	let options = args.options(settings);
+
+	// Containers would otherwise outlive an interrupted run
+	let interrupt_options = options.clone();
+	ctrlc::set_handler(move || {
+		let run_id = corrator::runtime::run_id();
+		if let Err(err) = corrator::cleanup(&interrupt_options, Some(run_id)) {
+			eprintln!("Unable to clean up containers: {err}");
+		}
+		exit(130);
+	})
+	.expect("Unable to handle interrupts");
+
+	let config = Config::new(containers, applications, options);
 
 	if !args.keep_eol_cache {
 		corrator::end_of_life::cache::clear().expect("Unable to clear EOL cache");
 	}
 
-	if let Ok(data) = config.run() {
-		match args.format.as_str() {
-			"text" => {
-				let data: String = data.into_iter().fold(String::new(), |mut output, b| {
-					write!(output, "{}\n\n", String::from(b)).expect("Unable to build output text");
-					output
-				});
-				write_results(data, args);
-			}
-			"json" => write_results(serde_json::to_string(&data).unwrap(), args),
-			_ => eprintln!("unknown format"),
+	let data = match config.run() {
+		Ok(x) => x,
+		Err(err) => {
+			eprintln!("Unable to query containers: {err}");
+			exit(1);
 		}
+	};
+
+	// Any app failing its version constraints fails the run, so it can gate a pipeline
+	let failed_checks = data
+		.iter()
+		.flat_map(|x| &x.apps)
+		.any(|x| matches!(x.check, Some(corrator::application::Check::Fail { .. })));
+
+	match args.format.as_str() {
+		"text" => {
+			let data: String = data.into_iter().fold(String::new(), |mut output, b| {
+				write!(output, "{}\n\n", String::from(b)).expect("Unable to build output text");
+				output
+			});
+			write_results(data, args);
+		}
+		"json" => write_results(serde_json::to_string(&data).unwrap(), args),
+		"cyclonedx-json" => write_results(corrator::sbom::cyclonedx(&data).to_string(), args),
+		"spdx-json" => write_results(corrator::sbom::spdx(&data).to_string(), args),
+		_ => eprintln!("unknown format"),
+	}
+
+	if failed_checks {
+		exit(1);
 	}
 }
 
@@ -179,6 +325,14 @@ fn parse_config_file<T: DeserializeOwned>(config_directory: &Path, file_name: &s
 	toml::from_str(&data).expect("Cound not read applications config file")
 }
 
+/// Settings from the optional settings.toml, or the defaults without one
+fn parse_settings_file(config_directory: &Path) -> Settings {
+	match config_directory.join("settings.toml").exists() {
+		true => parse_config_file(config_directory, "settings.toml"),
+		false => Settings::default(),
+	}
+}
+
 fn write_results(output: String, args: Args) {
 	let output = output.trim();
 
@@ -0,0 +1,488 @@
+use serde::Serialize;
+use std::{fmt, time::Duration};
+
+use crate::docker::DockerError;
+use crate::runtime::{ContainerRuntime, Probe};
+
+const DPKG_STATUS: &str = "/var/lib/dpkg/status";
+/// Distroless images keep a status file per package instead
+const DPKG_STATUS_DIR: &str = "/var/lib/dpkg/status.d";
+const APK_INSTALLED: &str = "/lib/apk/db/installed";
+const OS_RELEASE: &str = "/etc/os-release";
+/// Where os-release lives when `/etc/os-release` isn't there
+const OS_RELEASE_FALLBACK: &str = "/usr/lib/os-release";
+
+/// A package manager whose packages can be queried or inventoried
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Manager {
+	Dpkg,
+	Apk,
+	Rpm,
+	Pip,
+	Npm,
+	Gem,
+}
+
+impl fmt::Display for Manager {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Dpkg => write!(f, "dpkg"),
+			Self::Apk => write!(f, "apk"),
+			Self::Rpm => write!(f, "rpm"),
+			Self::Pip => write!(f, "pip"),
+			Self::Npm => write!(f, "npm"),
+			Self::Gem => write!(f, "gem"),
+		}
+	}
+}
+
+/// An installed package, found while taking an inventory
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Package {
+	pub name: String,
+	pub version: String,
+	pub manager: Manager,
+	/// The architecture an OS package was built for, e.g., "amd64"
+	pub arch: Option<String>,
+}
+
+/// The distribution a container is built from, as named by its os-release
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Distro {
+	/// e.g., "debian" or "alpine"
+	pub id: String,
+	/// e.g., "12" or "3.19.1"
+	pub version_id: Option<String>,
+}
+
+/// Why a package's version couldn't be found
+#[derive(Debug)]
+pub enum Error {
+	/// None of the supported package managers are present
+	NoManager,
+	/// The package manager doesn't list the package as installed
+	NotInstalled(Manager),
+	/// The runtime failed to read the package database
+	Runtime(DockerError),
+}
+
+/// Find an installed package's version with whichever package manager the
+/// container has
+///
+/// dpkg and apk databases are read as files, so they work on images without
+/// a shell. rpm's database can only be read by running `rpm`.
+pub fn query(
+	instance: &dyn ContainerRuntime,
+	package: &str,
+	probe: Probe,
+	prefix: &[String],
+	timeout: Option<Duration>,
+) -> Result<String, Error> {
+	let read = |path: &str| read(instance, path, timeout).map_err(Error::Runtime);
+
+	if let Some(status) = read(DPKG_STATUS)? {
+		return dpkg_version(&status, package).ok_or(Error::NotInstalled(Manager::Dpkg));
+	}
+
+	// Files there aren't always named after their package, so search them all
+	if let Some(status) = read_dir(instance, DPKG_STATUS_DIR, timeout).map_err(Error::Runtime)? {
+		return dpkg_version(&status, package).ok_or(Error::NotInstalled(Manager::Dpkg));
+	}
+
+	if let Some(installed) = read(APK_INSTALLED)? {
+		return apk_version(&installed, package).ok_or(Error::NotInstalled(Manager::Apk));
+	}
+
+	if probe == Probe::Files {
+		return Err(Error::NoManager);
+	}
+
+	let args = [
+		"rpm",
+		"-q",
+		"--queryformat",
+		"%{VERSION}-%{RELEASE}\\n",
+		package,
+	];
+	let output = instance
+		.execute(&[prefix, &args.map(String::from)].concat(), timeout)
+		.map_err(Error::Runtime)?;
+
+	// Several versions may be installed at once, e.g., kernels; report the first
+	match output.success() {
+		true => Ok(output
+			.stdout
+			.lines()
+			.next()
+			.unwrap_or_default()
+			.trim()
+			.to_string()),
+		false if output.stdout.contains("is not installed") => {
+			Err(Error::NotInstalled(Manager::Rpm))
+		}
+		false => Err(Error::NoManager),
+	}
+}
+
+/// List every OS and language package installed in a container
+///
+/// Package managers which aren't present are skipped. Only dpkg and apk can
+/// be inventoried when probing by files, since the rest have to be run.
+pub fn inventory(
+	instance: &dyn ContainerRuntime,
+	probe: Probe,
+	prefix: &[String],
+	timeout: Option<Duration>,
+) -> Result<Vec<Package>, DockerError> {
+	let mut packages = vec![];
+	let mut add = |manager: Manager, found: Vec<(String, String)>| {
+		packages.extend(found.into_iter().map(|(name, version)| Package {
+			name,
+			version,
+			manager,
+			arch: None,
+		}))
+	};
+	let mut os_packages = vec![];
+
+	let dpkg = match read(instance, DPKG_STATUS, timeout)? {
+		Some(x) => Some(x),
+		None => read_dir(instance, DPKG_STATUS_DIR, timeout)?,
+	};
+	let apk = read(instance, APK_INSTALLED, timeout)?;
+	let os_found = dpkg.is_some() || apk.is_some();
+
+	if let Some(status) = dpkg {
+		os_packages.extend(dpkg_packages(&status));
+	}
+	if let Some(installed) = apk {
+		os_packages.extend(apk_packages(&installed));
+	}
+
+	if probe == Probe::Files {
+		return Ok(os_packages);
+	}
+
+	let run = |args: &[&str]| -> Result<Option<String>, DockerError> {
+		let args: Vec<String> = args.iter().map(|x| String::from(*x)).collect();
+		match instance.execute(&[prefix, &args].concat(), timeout) {
+			Ok(x) if x.success() => Ok(Some(x.stdout)),
+			// Most likely the package manager isn't installed
+			Ok(_) => Ok(None),
+			Err(err) => Err(err),
+		}
+	};
+
+	if !os_found {
+		let format = "%{NAME} %{VERSION}-%{RELEASE} %{ARCH}\\n";
+		if let Some(x) = run(&["rpm", "-qa", "--queryformat", format])? {
+			os_packages.extend(rpm_packages(&x));
+		}
+	}
+
+	let pip = ["python3", "-m", "pip", "list", "--format=freeze"];
+	if let Some(x) = run(&pip)? {
+		add(Manager::Pip, pip_packages(&x));
+	}
+	if let Some(x) = run(&["npm", "ls", "--global", "--depth=0", "--json"])? {
+		add(Manager::Npm, npm_packages(&x));
+	}
+	if let Some(x) = run(&["gem", "list", "--local"])? {
+		add(Manager::Gem, gem_packages(&x));
+	}
+
+	Ok([os_packages, packages].concat())
+}
+
+/// Find the distribution a container is built from, or `None` if it has no
+/// os-release file naming one
+pub fn distro(
+	instance: &dyn ContainerRuntime,
+	timeout: Option<Duration>,
+) -> Result<Option<Distro>, DockerError> {
+	let release = match read(instance, OS_RELEASE, timeout)? {
+		Some(x) => Some(x),
+		None => read(instance, OS_RELEASE_FALLBACK, timeout)?,
+	};
+
+	Ok(release.as_deref().and_then(os_release))
+}
+
+/// Read the distribution from an os-release file
+///
+/// # Example
+/// ```rust
+/// # use corrator::package::os_release;
+/// let release = "\
+/// PRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"
+/// ID=debian
+/// VERSION_ID=\"12\"
+/// ";
+///
+/// let distro = os_release(release).unwrap();
+/// assert_eq!(distro.id, "debian");
+/// assert_eq!(distro.version_id.as_deref(), Some("12"));
+/// assert_eq!(os_release("NAME=Unknown"), None);
+/// ```
+pub fn os_release(release: &str) -> Option<Distro> {
+	let field = |name: &str| {
+		release
+			.lines()
+			.find_map(|x| x.trim().strip_prefix(name)?.strip_prefix('='))
+			.map(|x| String::from(x.trim_matches(['"', '\''])))
+			.filter(|x| !x.is_empty())
+	};
+
+	Some(Distro {
+		id: field("ID")?,
+		version_id: field("VERSION_ID"),
+	})
+}
+
+/// Read a file, or `None` if it doesn't exist
+///
+/// Other errors, e.g., timeouts, say nothing about the file, so are kept.
+fn read(
+	instance: &dyn ContainerRuntime,
+	path: &str,
+	timeout: Option<Duration>,
+) -> Result<Option<String>, DockerError> {
+	match instance.read_file(path, timeout) {
+		Ok(x) => Ok(Some(String::from_utf8_lossy(&x).into_owned())),
+		Err(DockerError::FileNotFound(_)) => Ok(None),
+		Err(err) => Err(err),
+	}
+}
+
+/// Read every file in a directory as one, or `None` if it doesn't exist
+fn read_dir(
+	instance: &dyn ContainerRuntime,
+	path: &str,
+	timeout: Option<Duration>,
+) -> Result<Option<String>, DockerError> {
+	match instance.read_dir(path, timeout) {
+		// Files may not end with a blank line, so separate their stanzas
+		Ok(x) => Ok(Some(
+			x.iter()
+				.map(|x| String::from_utf8_lossy(x))
+				.collect::<Vec<_>>()
+				.join("\n\n"),
+		)),
+		Err(DockerError::FileNotFound(_)) => Ok(None),
+		Err(err) => Err(err),
+	}
+}
+
+/// Find a package's version in a dpkg status file
+///
+/// # Example
+/// ```rust
+/// # use corrator::package::dpkg_version;
+/// let status = "\
+/// Package: libc6
+/// Status: install ok installed
+/// Version: 2.35-0ubuntu3.6
+///
+/// Package: openssl
+/// Status: deinstall ok config-files
+/// Version: 3.0.2-0ubuntu1.15
+/// ";
+///
+/// assert_eq!(dpkg_version(status, "libc6").as_deref(), Some("2.35-0ubuntu3.6"));
+/// assert_eq!(dpkg_version(status, "openssl"), None);
+/// ```
+pub fn dpkg_version(status: &str, package: &str) -> Option<String> {
+	find(dpkg_packages(status), package)
+}
+
+/// Every installed package in a dpkg status file
+pub fn dpkg_packages(status: &str) -> Vec<Package> {
+	stanzas(status)
+		.filter_map(|fields| {
+			let field = |name: &str| fields.iter().find(|(k, _)| *k == name).map(|(_, v)| *v);
+			// Per package status files (e.g., in distroless images) have no status field
+			let installed = field("Status").is_none_or(|x| x.ends_with(" installed"));
+
+			match (field("Package"), field("Version"), installed) {
+				(Some(name), Some(version), true) => Some(Package {
+					name: name.into(),
+					version: version.into(),
+					manager: Manager::Dpkg,
+					arch: field("Architecture").map(String::from),
+				}),
+				_ => None,
+			}
+		})
+		.collect()
+}
+
+/// Find a package's version in an apk installed database
+///
+/// # Example
+/// ```rust
+/// # use corrator::package::apk_version;
+/// let installed = "\
+/// P:musl
+/// V:1.2.4-r2
+///
+/// P:openssl
+/// V:3.1.4-r5
+/// ";
+///
+/// assert_eq!(apk_version(installed, "openssl").as_deref(), Some("3.1.4-r5"));
+/// assert_eq!(apk_version(installed, "bash"), None);
+/// ```
+pub fn apk_version(installed: &str, package: &str) -> Option<String> {
+	find(apk_packages(installed), package)
+}
+
+/// Every package in an apk installed database
+pub fn apk_packages(installed: &str) -> Vec<Package> {
+	installed
+		.split("\n\n")
+		.filter_map(|record| {
+			let field = |name: &str| record.lines().find_map(|x| x.strip_prefix(name));
+
+			Some(Package {
+				name: field("P:")?.into(),
+				version: field("V:")?.into(),
+				manager: Manager::Apk,
+				arch: field("A:").map(String::from),
+			})
+		})
+		.collect()
+}
+
+/// Packages from `rpm -qa`, formatted as one "name version arch" per line
+///
+/// Multilib hosts install a package once per arch, e.g., i686 and x86_64.
+fn rpm_packages(output: &str) -> Vec<Package> {
+	output
+		.lines()
+		.filter_map(|x| {
+			let mut fields = x.split_whitespace();
+
+			Some(Package {
+				name: fields.next()?.into(),
+				version: fields.next()?.into(),
+				manager: Manager::Rpm,
+				arch: fields.next().map(String::from),
+			})
+		})
+		.collect()
+}
+
+/// Packages from `pip list --format=freeze`, e.g., "requests==2.31.0"
+///
+/// Editable and direct URL installs have no version, so are left out.
+fn pip_packages(output: &str) -> Vec<(String, String)> {
+	output
+		.lines()
+		.filter_map(|x| x.trim().split_once("=="))
+		.map(|(name, version)| (name.into(), version.into()))
+		.collect()
+}
+
+/// Packages from `npm ls --json`
+fn npm_packages(output: &str) -> Vec<(String, String)> {
+	let Ok(tree) = serde_json::from_str::<serde_json::Value>(output) else {
+		return vec![];
+	};
+
+	tree["dependencies"]
+		.as_object()
+		.into_iter()
+		.flatten()
+		.filter_map(|(name, x)| Some((name.clone(), x["version"].as_str()?.into())))
+		.collect()
+}
+
+/// Packages from `gem list`, e.g., "rake (13.0.6, 12.3.3)" or
+/// "json (default: 2.6.1)"; only the newest of several versions is kept
+fn gem_packages(output: &str) -> Vec<(String, String)> {
+	output
+		.lines()
+		.filter_map(|x| {
+			let (name, versions) = x.trim().strip_suffix(')')?.split_once(" (")?;
+			let version = versions.split(", ").next()?;
+			let version = version.strip_prefix("default: ").unwrap_or(version);
+
+			Some((name.into(), version.into()))
+		})
+		.collect()
+}
+
+fn find(packages: Vec<Package>, package: &str) -> Option<String> {
+	packages
+		.into_iter()
+		.find(|x| x.name == package)
+		.map(|x| x.version)
+}
+
+/// Split a Debian control file into its stanzas of (name, value) fields
+fn stanzas(control: &str) -> impl Iterator<Item = Vec<(&str, &str)>> {
+	control.split("\n\n").map(|stanza| {
+		stanza
+			.lines()
+			// Continuation lines start with whitespace, and are never needed here
+			.filter(|x| !x.starts_with([' ', '\t']))
+			.filter_map(|x| x.split_once(':'))
+			.map(|(k, v)| (k.trim(), v.trim()))
+			.collect()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_language_packages() {
+		assert_eq!(
+			pip_packages("requests==2.31.0\n-e /src/app\nurllib3==2.0.7\n"),
+			[
+				(String::from("requests"), String::from("2.31.0")),
+				(String::from("urllib3"), String::from("2.0.7"))
+			]
+		);
+		assert_eq!(
+			npm_packages(r#"{"dependencies": {"npm": {"version": "10.2.4"}}}"#),
+			[(String::from("npm"), String::from("10.2.4"))]
+		);
+		assert_eq!(
+			gem_packages("json (default: 2.6.1)\nrake (13.0.6, 12.3.3)\n"),
+			[
+				(String::from("json"), String::from("2.6.1")),
+				(String::from("rake"), String::from("13.0.6"))
+			]
+		);
+		assert_eq!(
+			rpm_packages("glibc 2.34-100.el9 i686\nglibc 2.34-100.el9 x86_64\n")
+				.iter()
+				.map(|x| (x.name.as_str(), x.version.as_str(), x.arch.as_deref()))
+				.collect::<Vec<_>>(),
+			[
+				("glibc", "2.34-100.el9", Some("i686")),
+				("glibc", "2.34-100.el9", Some("x86_64"))
+			]
+		);
+		assert!(npm_packages("npm: not found").is_empty());
+	}
+
+	#[test]
+	fn skips_dpkg_continuation_lines() {
+		let status = "\
+Package: tzdata
+Status: install ok installed
+Description: time zone and daylight-saving time data
+ Version: not a field
+Version: 2024a-0ubuntu0.22.04
+";
+
+		assert_eq!(
+			dpkg_version(status, "tzdata").as_deref(),
+			Some("2024a-0ubuntu0.22.04")
+		);
+	}
+}
@@ -0,0 +1,160 @@
+use crate::docker::{Docker, DockerError};
+use crate::runtime::{ContainerRuntime, ExecOutput, Image, Settings};
+use std::{cell::OnceCell, process::Command, time::Duration};
+
+/// A podman backed runtime, suitable for rootless hosts without a docker daemon
+///
+/// Podman's CLI is close enough to docker's that most of the lifecycle is
+/// shared with [`Docker`]. The differences are handled here:
+///
+/// - Short image names are qualified against docker.io, since podman will
+///   otherwise refuse to pick a registry when it has no TTY to prompt on.
+///   Names already present locally, e.g., images built with buildah, which
+///   podman stores as `localhost/<name>`, are left alone.
+/// - Image removal ignores images which are already gone, which is common
+///   with rootless storage where another user's images aren't visible.
+/// - An alternative storage root can be given for rootless hosts where the
+///   default per-user store isn't suitable (e.g., a small home volume).
+pub struct Podman<'a> {
+	inner: Docker<'a>,
+	/// The runtime for the image by its resolved name, found on first use
+	/// since finding it runs podman
+	resolved: OnceCell<Docker<'a>>,
+}
+
+impl Podman<'_> {
+	pub fn new<'a>(
+		name: &'a str,
+		path: &str,
+		settings: Settings,
+		storage_root: Option<&str>,
+	) -> Podman<'a> {
+		let global_args = match storage_root {
+			Some(x) => vec![String::from("--root"), shellexpand::tilde(x).into_owned()],
+			None => vec![],
+		};
+
+		Podman {
+			inner: Docker::with_program(name, path, settings, "podman", global_args),
+			resolved: OnceCell::new(),
+		}
+	}
+
+	fn resolved(&self) -> &Docker<'_> {
+		self.resolved.get_or_init(|| {
+			let path = resolve(self.inner.command(), self.inner.path());
+			self.inner.with_path(&path)
+		})
+	}
+
+	/// Remove containers created by corrator; see [`Docker::reap`]
+	pub fn reap(&self, run_id: Option<&str>) -> Result<Vec<String>, DockerError> {
+		self.inner.reap(run_id)
+	}
+}
+
+impl ContainerRuntime for Podman<'_> {
+	fn pull(&self) -> Result<(), DockerError> {
+		self.resolved().pull()
+	}
+
+	fn inspect(&self) -> Result<Image, DockerError> {
+		self.inner.inspect()
+	}
+
+	fn run(&self) -> Result<(), DockerError> {
+		self.resolved().run()
+	}
+
+	fn execute(
+		&self,
+		args: &[String],
+		timeout: Option<Duration>,
+	) -> Result<ExecOutput, DockerError> {
+		self.inner.execute(args, timeout)
+	}
+
+	fn read_file(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<u8>, DockerError> {
+		self.inner.read_file(path, timeout)
+	}
+
+	fn read_dir(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<Vec<u8>>, DockerError> {
+		self.inner.read_dir(path, timeout)
+	}
+
+	fn stop(&self) -> Result<(), DockerError> {
+		self.inner.stop()
+	}
+
+	fn clean(&self) -> Result<(), DockerError> {
+		let output = self
+			.inner
+			.command()
+			.args(["rmi", "--force", "--ignore", self.resolved().path()])
+			.output()?;
+
+		match output.status.success() {
+			true => Ok(()),
+			false => Err(DockerError::from(&output)),
+		}
+	}
+}
+
+/// The name to use for an image; short names are qualified, unless an image
+/// with that name is already present, according to `podman`
+fn resolve(mut podman: Command, path: &str) -> String {
+	let qualified = qualify(path);
+	if qualified == path {
+		return qualified;
+	}
+
+	let exists = podman
+		.args(["image", "exists", path])
+		.output()
+		.is_ok_and(|x| x.status.success());
+
+	match exists {
+		true => String::from(path),
+		false => qualified,
+	}
+}
+
+/// Qualify a short image name the way docker would resolve it
+///
+/// # Example
+/// ```rust
+/// # use corrator::podman::qualify;
+/// assert_eq!(qualify("ubuntu"), "docker.io/library/ubuntu");
+/// assert_eq!(qualify("natecox/corrator"), "docker.io/natecox/corrator");
+/// assert_eq!(qualify("ghcr.io/natecox/corrator"), "ghcr.io/natecox/corrator");
+/// ```
+pub fn qualify(path: &str) -> String {
+	match path.split_once('/') {
+		Some((domain, _))
+			if domain.contains('.') || domain.contains(':') || domain == "localhost" =>
+		{
+			String::from(path)
+		}
+		Some(_) => format!("docker.io/{path}"),
+		None => format!("docker.io/library/{path}"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn local_images_keep_short_names() {
+		// `true` and `false` stand in for `podman image exists`
+		assert_eq!(resolve(Command::new("true"), "myapp:dev"), "myapp:dev");
+		assert_eq!(
+			resolve(Command::new("false"), "myapp:dev"),
+			"docker.io/library/myapp:dev"
+		);
+		assert_eq!(
+			resolve(Command::new("false"), "localhost/myapp"),
+			"localhost/myapp"
+		);
+	}
+}
@@ -0,0 +1,851 @@
+use clap::ValueEnum;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::docker::DockerError;
+use crate::podman::qualify;
+
+/// When to pull an image from its registry before running it
+#[derive(ValueEnum, Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PullPolicy {
+	/// Always pull, to be sure the latest image is queried
+	#[default]
+	Always,
+	/// Only pull if the image isn't already present locally
+	IfMissing,
+	/// Never pull; the image must already be present, e.g., a local build
+	Never,
+}
+
+/// How applications inside a container are probed for their versions
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Probe {
+	/// Start the container and run version commands inside it
+	#[default]
+	Exec,
+	/// Create the container without starting it and only read files out of
+	/// it, for images without a shell such as distroless or scratch
+	Files,
+}
+
+/// Limits, in seconds, on how long each step of a query may take
+///
+/// Steps without a limit may take as long as they need. A limit of 0 would
+/// time out every step at once, so is rejected rather than read as none.
+///
+/// # Example
+/// ```rust
+/// # use corrator::runtime::Timeouts;
+/// let timeouts: Timeouts = toml::from_str("exec = 10").unwrap();
+///
+/// assert_eq!(timeouts.exec, Some(10));
+/// assert!(toml::from_str::<Timeouts>("exec = 0").is_err());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Timeouts {
+	/// Pulling the image
+	#[serde(default, deserialize_with = "seconds")]
+	pub pull: Option<u64>,
+	/// Creating and starting the container
+	#[serde(default, deserialize_with = "seconds")]
+	pub start: Option<u64>,
+	/// Each version command or file read
+	#[serde(default, deserialize_with = "seconds")]
+	pub exec: Option<u64>,
+}
+
+/// Deserialize a timeout, which must be at least a second
+pub(crate) fn seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+	match Option::<u64>::deserialize(deserializer)? {
+		Some(0) => Err(D::Error::custom(
+			"timeouts must be at least 1 second; leave them out to never time out",
+		)),
+		x => Ok(x),
+	}
+}
+
+impl Timeouts {
+	/// Fill in any limits not set here from `fallback`
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::runtime::Timeouts;
+	/// let container = Timeouts { pull: Some(600), ..Default::default() };
+	/// let global = Timeouts { pull: Some(60), exec: Some(10), ..Default::default() };
+	///
+	/// assert_eq!(
+	///     container.or(global),
+	///     Timeouts { pull: Some(600), start: None, exec: Some(10) }
+	/// );
+	/// ```
+	pub fn or(self, fallback: Self) -> Self {
+		Self {
+			pull: self.pull.or(fallback.pull),
+			start: self.start.or(fallback.start),
+			exec: self.exec.or(fallback.exec),
+		}
+	}
+
+	pub fn pull(&self) -> Option<Duration> {
+		self.pull.map(Duration::from_secs)
+	}
+
+	pub fn start(&self) -> Option<Duration> {
+		self.start.map(Duration::from_secs)
+	}
+
+	pub fn exec(&self) -> Option<Duration> {
+		self.exec.map(Duration::from_secs)
+	}
+}
+
+/// How to retry pulls and version commands which fail for transient reasons
+///
+/// See [`DockerError::is_transient`] for which failures are retried.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Retries {
+	/// How many times to retry after the first attempt
+	pub count: u32,
+	/// Seconds to wait before the first retry, doubling for each one after
+	pub backoff: f64,
+}
+
+impl Default for Retries {
+	fn default() -> Self {
+		Self {
+			count: 0,
+			backoff: 1.0,
+		}
+	}
+}
+
+/// Per-container settings for a runtime, resolved from config and options
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+	pub pull_policy: PullPolicy,
+	pub probe: Probe,
+	pub timeouts: Timeouts,
+
+	/// The platform to pull and run, e.g., "linux/arm64"; defaults to the host's
+	pub platform: Option<String>,
+
+	/// The digest the image must resolve to, if it's pinned
+	pub digest: Option<String>,
+
+	/// Environment variables for the container and its commands
+	pub env: BTreeMap<String, String>,
+	/// The user to run commands as, e.g., "nobody" or "1000:1000"
+	pub user: Option<String>,
+	/// The directory to run commands in
+	pub workdir: Option<String>,
+	/// The container's network mode; defaults to "none", so probes can't reach out
+	pub network: Option<String>,
+	/// Volumes to mount, e.g., "/opt/certs:/certs:ro"
+	pub volumes: Vec<String>,
+	/// Further arguments for the runtime's `run` or `create` command
+	pub run_args: Vec<String>,
+
+	/// Start the container with its image's entrypoint, rather than a bare shell
+	pub keep_entrypoint: bool,
+	/// Shell commands to set up the environment before each version command
+	pub init: Option<String>,
+
+	/// List every installed package, as well as querying apps
+	pub inventory: bool,
+}
+
+impl Settings {
+	/// The network mode to run containers with
+	pub fn network(&self) -> &str {
+		self.network.as_deref().unwrap_or("none")
+	}
+
+	/// Arguments to run version commands through, so they see the same
+	/// environment as the image's entrypoint and init commands set up
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::runtime::Settings;
+	/// let settings = Settings {
+	///     keep_entrypoint: true,
+	///     init: Some(String::from(". ~/.nvm/nvm.sh")),
+	///     ..Default::default()
+	/// };
+	/// let entrypoint = [String::from("/entrypoint.sh")];
+	///
+	/// assert_eq!(
+	///     settings.command_prefix(&entrypoint),
+	///     ["/entrypoint.sh", "sh", "-c", ". ~/.nvm/nvm.sh\nexec \"$@\"", "sh"]
+	/// );
+	/// assert!(Settings::default().command_prefix(&entrypoint).is_empty());
+	/// ```
+	pub fn command_prefix(&self, entrypoint: &[String]) -> Vec<String> {
+		let mut prefix = vec![];
+
+		if self.keep_entrypoint {
+			prefix.extend_from_slice(entrypoint);
+		}
+		if let Some(x) = &self.init {
+			let script = format!("{x}\nexec \"$@\"");
+			prefix.extend([
+				String::from("sh"),
+				String::from("-c"),
+				script,
+				String::from("sh"),
+			]);
+		}
+
+		prefix
+	}
+
+	/// Environment variables as "NAME=value" pairs
+	pub fn env_pairs(&self) -> Vec<String> {
+		self.env.iter().map(|(k, v)| format!("{k}={v}")).collect()
+	}
+}
+
+/// Which output stream(s) of a command to read
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Stream {
+	#[default]
+	Stdout,
+	Stderr,
+	Both,
+}
+
+/// The result of running a command inside a container
+#[derive(Debug, Default, Clone)]
+pub struct ExecOutput {
+	pub stdout: String,
+	pub stderr: String,
+
+	/// The command's exit code, if it wasn't terminated by a signal
+	pub status: Option<i32>,
+}
+
+impl ExecOutput {
+	/// Whether the command exited successfully
+	pub fn success(&self) -> bool {
+		self.status == Some(0)
+	}
+
+	/// The text of the requested stream(s); "both" is stdout followed by stderr
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::runtime::{ExecOutput, Stream};
+	/// let output = ExecOutput {
+	///     stdout: String::from("out\n"),
+	///     stderr: String::from("err\n"),
+	///     status: Some(0),
+	/// };
+	///
+	/// assert_eq!(output.stream(Stream::Stderr), "err\n");
+	/// assert_eq!(output.stream(Stream::Both), "out\nerr\n");
+	/// ```
+	pub fn stream(&self, stream: Stream) -> String {
+		match stream {
+			Stream::Stdout => self.stdout.clone(),
+			Stream::Stderr => self.stderr.clone(),
+			Stream::Both => format!("{}{}", self.stdout, self.stderr),
+		}
+	}
+}
+
+/// Metadata about the exact image a container was queried from
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Image {
+	/// The registry digest the image was pulled by, e.g., "sha256:..."
+	pub digest: Option<String>,
+	/// Every registry digest the image is known by in its repository, since
+	/// the same image can be pulled by a tag and by its platform's manifest
+	#[serde(skip)]
+	pub digests: Vec<String>,
+	/// The digest of the image's config, as shown by `docker images`
+	pub id: String,
+	pub created: Option<String>,
+	/// Size in bytes
+	pub size: Option<u64>,
+	pub os: Option<String>,
+	pub architecture: Option<String>,
+	pub variant: Option<String>,
+	/// OCI labels, e.g., `org.opencontainers.image.version`
+	pub labels: BTreeMap<String, String>,
+	/// The command the image starts with, before its arguments
+	pub entrypoint: Vec<String>,
+}
+
+impl Image {
+	/// Read the output of `docker image inspect` or the Engine API equivalent
+	///
+	/// `path` picks out the matching repo digest, if the image has several.
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::runtime::Image;
+	/// # use serde_json::json;
+	/// let inspect = json!({
+	///     "Id": "sha256:abc",
+	///     "RepoDigests": ["ubuntu@sha256:def"],
+	///     "Os": "linux",
+	///     "Architecture": "amd64",
+	///     "Config": { "Labels": { "org.opencontainers.image.version": "22.04" } },
+	/// });
+	/// let image = Image::from_inspect(&inspect, "ubuntu:22.04");
+	///
+	/// assert_eq!(image.digest.as_deref(), Some("sha256:def"));
+	/// assert_eq!(image.labels["org.opencontainers.image.version"], "22.04");
+	///
+	/// // Only digests of the exact repository can verify the image
+	/// let inspect = json!({
+	///     "RepoDigests": [
+	///         "myubuntu@sha256:abc",
+	///         "ubuntu@sha256:def",
+	///         "docker.io/library/ubuntu@sha256:fed",
+	///     ],
+	/// });
+	/// let image = Image::from_inspect(&inspect, "docker.io/library/ubuntu");
+	///
+	/// assert_eq!(image.digests, ["sha256:def", "sha256:fed"]);
+	/// ```
+	pub fn from_inspect(inspect: &Value, path: &str) -> Self {
+		let text = |x: &Value| x.as_str().filter(|x| !x.is_empty()).map(String::from);
+		let repository = qualify(repository(path));
+		let digests: Vec<(&str, &str)> = inspect["RepoDigests"]
+			.as_array()
+			.into_iter()
+			.flatten()
+			.filter_map(|x| x.as_str()?.split_once('@'))
+			.collect();
+		// Only the exact repository counts, e.g., not "myubuntu" for "ubuntu"
+		let matching: Vec<String> = digests
+			.iter()
+			.filter(|(x, _)| qualify(x) == repository)
+			.map(|(_, x)| String::from(*x))
+			.collect();
+
+		Self {
+			digest: matching
+				.first()
+				.cloned()
+				.or_else(|| digests.first().map(|(_, x)| String::from(*x))),
+			digests: matching,
+			id: text(&inspect["Id"]).unwrap_or_default(),
+			created: text(&inspect["Created"]),
+			size: inspect["Size"].as_u64(),
+			os: text(&inspect["Os"]),
+			architecture: text(&inspect["Architecture"]),
+			variant: text(&inspect["Variant"]),
+			labels: labels(&inspect["Config"]["Labels"]),
+			entrypoint: strings(&inspect["Config"]["Entrypoint"]),
+		}
+	}
+
+	/// The image's platform, e.g., "linux/arm64/v8"
+	pub fn platform(&self) -> Option<String> {
+		let platform = [&self.os, &self.architecture, &self.variant]
+			.into_iter()
+			.flatten()
+			.cloned()
+			.collect::<Vec<_>>()
+			.join("/");
+
+		Some(platform).filter(|x| !x.is_empty())
+	}
+
+	/// Whether the image is for a platform, e.g., "linux/arm64"
+	///
+	/// A variant is only compared when both sides have one, since engines
+	/// often leave it out.
+	///
+	/// # Example
+	/// ```rust
+	/// # use corrator::runtime::Image;
+	/// let image = Image {
+	///     os: Some(String::from("linux")),
+	///     architecture: Some(String::from("arm64")),
+	///     ..Default::default()
+	/// };
+	///
+	/// assert!(image.matches_platform("linux/arm64/v8"));
+	/// assert!(!image.matches_platform("linux/amd64"));
+	/// ```
+	pub fn matches_platform(&self, platform: &str) -> bool {
+		let mut wanted = platform.split('/');
+		let os = wanted.next();
+		let architecture = wanted.next();
+		let variant = wanted.next();
+
+		self.os.as_deref() == os
+			&& self.architecture.as_deref() == architecture
+			&& match (variant, self.variant.as_deref()) {
+				(Some(x), Some(y)) => x == y,
+				_ => true,
+			}
+	}
+}
+
+/// An image reference without its tag or digest, e.g., "ubuntu" for "ubuntu:22.04"
+fn repository(path: &str) -> &str {
+	let repository = path.split('@').next().unwrap_or(path);
+
+	match repository.rsplit_once(':') {
+		Some((x, tag)) if !tag.contains('/') => x,
+		_ => repository,
+	}
+}
+
+/// Read a JSON array of strings, e.g., an image config's `Entrypoint`
+pub(crate) fn strings(value: &Value) -> Vec<String> {
+	value
+		.as_array()
+		.into_iter()
+		.flatten()
+		.filter_map(|x| x.as_str().map(String::from))
+		.collect()
+}
+
+/// Read a JSON object of labels, e.g., an image config's `Labels`
+pub(crate) fn labels(value: &Value) -> BTreeMap<String, String> {
+	value
+		.as_object()
+		.into_iter()
+		.flatten()
+		.filter_map(|(k, v)| Some((k.clone(), String::from(v.as_str()?))))
+		.collect()
+}
+
+/// The lifecycle of a container used to query application versions
+///
+/// Implemented by each supported container engine (see [`Docker`]) so that
+/// [`Config`] doesn't need to know which binary or API it is talking to.
+///
+/// [`Docker`]: ../docker/struct.Docker.html
+/// [`Config`]: ../struct.Config.html
+pub trait ContainerRuntime {
+	/// Make sure the image is available locally, according to the pull policy
+	fn pull(&self) -> Result<(), DockerError>;
+
+	/// Create a container from the already pulled image
+	///
+	/// The container is started with a long running shell, unless it is
+	/// being probed by files only.
+	fn run(&self) -> Result<(), DockerError>;
+
+	/// Describe the image the container was created from
+	fn inspect(&self) -> Result<Image, DockerError>;
+
+	/// Run a command inside the running container, returning its output
+	///
+	/// A command which runs but exits unsuccessfully is not an error here;
+	/// its exit status is part of the returned output. One which outlasts
+	/// `timeout` is, though.
+	fn execute(
+		&self,
+		args: &[String],
+		timeout: Option<Duration>,
+	) -> Result<ExecOutput, DockerError>;
+
+	/// Read a file out of the container, following symlinks
+	fn read_file(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<u8>, DockerError>;
+
+	/// Read every regular file directly inside a directory of the container
+	fn read_dir(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<Vec<u8>>, DockerError>;
+
+	/// Stop and remove the container
+	fn stop(&self) -> Result<(), DockerError>;
+
+	/// Remove the image the container was started from
+	fn clean(&self) -> Result<(), DockerError>;
+}
+
+/// Retries another runtime's pulls and probes, counting the attempts made
+///
+/// Starting and stopping containers aren't retried, since a partly
+/// successful attempt would leave a conflicting container behind.
+pub struct Retrying<'a> {
+	inner: &'a dyn ContainerRuntime,
+	retries: Retries,
+	attempts: Cell<u32>,
+}
+
+impl<'a> Retrying<'a> {
+	pub fn new(inner: &'a dyn ContainerRuntime, retries: Retries) -> Self {
+		Self {
+			inner,
+			retries,
+			attempts: Cell::new(0),
+		}
+	}
+
+	/// The most attempts any one step took since this was last called
+	///
+	/// A probe may take several steps, e.g., reading each package database,
+	/// so only retries of a single step add to the count.
+	pub fn take_attempts(&self) -> u32 {
+		self.attempts.take()
+	}
+
+	fn retry<T>(&self, mut step: impl FnMut() -> Result<T, DockerError>) -> Result<T, DockerError> {
+		// Backoffs which aren't a duration, e.g., negative ones, don't wait
+		let mut backoff = Duration::try_from_secs_f64(self.retries.backoff).unwrap_or_default();
+		let mut retries = 0;
+
+		loop {
+			self.attempts.set(self.attempts.get().max(retries + 1));
+
+			match step() {
+				Err(err) if err.is_transient() && retries < self.retries.count => {
+					thread::sleep(backoff);
+					backoff = backoff.saturating_mul(2);
+					retries += 1;
+				}
+				result => return result,
+			}
+		}
+	}
+}
+
+impl ContainerRuntime for Retrying<'_> {
+	fn pull(&self) -> Result<(), DockerError> {
+		self.retry(|| self.inner.pull())
+	}
+
+	fn inspect(&self) -> Result<Image, DockerError> {
+		self.inner.inspect()
+	}
+
+	fn run(&self) -> Result<(), DockerError> {
+		self.inner.run()
+	}
+
+	fn execute(
+		&self,
+		args: &[String],
+		timeout: Option<Duration>,
+	) -> Result<ExecOutput, DockerError> {
+		self.retry(|| self.inner.execute(args, timeout))
+	}
+
+	fn read_file(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<u8>, DockerError> {
+		self.retry(|| self.inner.read_file(path, timeout))
+	}
+
+	fn read_dir(&self, path: &str, timeout: Option<Duration>) -> Result<Vec<Vec<u8>>, DockerError> {
+		self.retry(|| self.inner.read_dir(path, timeout))
+	}
+
+	fn stop(&self) -> Result<(), DockerError> {
+		self.inner.stop()
+	}
+
+	fn clean(&self) -> Result<(), DockerError> {
+		self.inner.clean()
+	}
+}
+
+/// Label applied to every container corrator creates, valued with the [`run_id`]
+pub const LABEL: &str = "rs.corrator.run";
+
+/// Label valued with the ID of the process which created a container
+pub const PID_LABEL: &str = "rs.corrator.pid";
+
+/// Label valued with the name of the host which created a container
+pub const HOST_LABEL: &str = "rs.corrator.host";
+
+/// Labels applied to every container corrator creates, by name
+pub(crate) fn container_labels() -> Vec<(&'static str, String)> {
+	let mut found = vec![
+		(LABEL, String::from(run_id())),
+		(PID_LABEL, std::process::id().to_string()),
+	];
+	found.extend(host_name().map(|x| (HOST_LABEL, String::from(x))));
+	found
+}
+
+/// Whether a container, by its labels, may still be used by the corrator
+/// process which created it
+///
+/// Containers from other hosts, e.g., sharing a remote engine, can't be
+/// checked, so they're always in use, as is anything else which can't be
+/// checked. Containers without a process label can't be claimed by anyone.
+pub(crate) fn is_in_use(labels: &Value) -> bool {
+	let Some(pid) = labels[PID_LABEL].as_str() else {
+		return false;
+	};
+
+	if let Some(host) = labels[HOST_LABEL].as_str() {
+		if host_name() != Some(host) {
+			return true;
+		}
+	}
+
+	let Ok(pid) = pid.parse::<u32>() else {
+		return true;
+	};
+
+	if Path::new("/proc/self").exists() {
+		return Path::new(&format!("/proc/{pid}"))
+			.try_exists()
+			.unwrap_or(true);
+	}
+
+	// Without procfs, only a missing process is known to be gone; e.g., one
+	// belonging to another user can't be signalled, but is still running
+	match Command::new("kill").args(["-0", &pid.to_string()]).output() {
+		Ok(x) if !x.status.success() => {
+			!String::from_utf8_lossy(&x.stderr).contains("No such process")
+		}
+		_ => true,
+	}
+}
+
+/// The name of this host, if it can be found
+fn host_name() -> Option<&'static str> {
+	static HOST_NAME: OnceLock<Option<String>> = OnceLock::new();
+
+	HOST_NAME
+		.get_or_init(|| {
+			let found = ["/proc/sys/kernel/hostname", "/etc/hostname"]
+				.into_iter()
+				.find_map(|x| std::fs::read_to_string(x).ok())
+				.or_else(|| {
+					let output = Command::new("hostname").output().ok()?;
+					output
+						.status
+						.success()
+						.then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+				})?;
+
+			Some(found.trim().to_string()).filter(|x| !x.is_empty())
+		})
+		.as_deref()
+}
+
+/// Identifies the containers created by this process
+///
+/// Used so an interrupted run only removes its own containers, and not
+/// those of another corrator process sharing the same engine.
+pub fn run_id() -> &'static str {
+	static RUN_ID: OnceLock<String> = OnceLock::new();
+
+	RUN_ID.get_or_init(|| {
+		let started = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_millis();
+		format!("{started:x}{:x}", std::process::id())
+	})
+}
+
+/// A unique container name for a configured container
+///
+/// # Example
+/// ```rust
+/// # use corrator::runtime::instance_name;
+/// let name = instance_name("my app", Some("linux/arm64"));
+///
+/// assert!(name.starts_with("corrator-my-app-linux-arm64-"));
+/// assert_ne!(name, instance_name("my app", Some("linux/arm64")));
+/// ```
+pub fn instance_name(name: &str, platform: Option<&str>) -> String {
+	static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+	let name = match platform {
+		Some(x) => format!("{name}-{x}"),
+		None => String::from(name),
+	};
+	// Engines only accept [a-zA-Z0-9][a-zA-Z0-9_.-]* as container names
+	let name: String = name
+		.chars()
+		.map(|x| match x.is_ascii_alphanumeric() || "_.-".contains(x) {
+			true => x,
+			false => '-',
+		})
+		.collect();
+
+	format!(
+		"corrator-{name}-{}-{}",
+		run_id(),
+		COUNTER.fetch_add(1, Ordering::Relaxed)
+	)
+}
+
+/// Stops and removes a container when dropped, e.g., while unwinding a panic
+///
+/// Use [`Guard::stop`] to stop the container and see whether that failed.
+pub struct Guard<'a> {
+	instance: &'a dyn ContainerRuntime,
+	stopped: bool,
+}
+
+impl<'a> Guard<'a> {
+	pub fn new(instance: &'a dyn ContainerRuntime) -> Self {
+		Self {
+			instance,
+			stopped: false,
+		}
+	}
+
+	/// Stop and remove the container now, rather than on drop
+	pub fn stop(mut self) -> Result<(), DockerError> {
+		self.stopped = true;
+		self.instance.stop()
+	}
+}
+
+impl Drop for Guard<'_> {
+	fn drop(&mut self) {
+		if !self.stopped {
+			// Nothing can be reported from here; `corrator cleanup` reaps leftovers
+			let _ = self.instance.stop();
+		}
+	}
+}
+
+/// Limits how many threads may do something at once, e.g., pull images
+pub struct Semaphore {
+	permits: Mutex<usize>,
+	released: Condvar,
+}
+
+impl Semaphore {
+	pub fn new(permits: usize) -> Self {
+		Self {
+			permits: Mutex::new(permits),
+			released: Condvar::new(),
+		}
+	}
+
+	/// Wait for a permit, which is given back when dropped
+	pub fn acquire(&self) -> Permit<'_> {
+		let mut permits = self.permits.lock().unwrap_or_else(|x| x.into_inner());
+		while *permits == 0 {
+			permits = self
+				.released
+				.wait(permits)
+				.unwrap_or_else(|x| x.into_inner());
+		}
+		*permits -= 1;
+
+		Permit(self)
+	}
+}
+
+/// Permission from a [`Semaphore`] to go ahead
+pub struct Permit<'a>(&'a Semaphore);
+
+impl Drop for Permit<'_> {
+	fn drop(&mut self) {
+		*self.0.permits.lock().unwrap_or_else(|x| x.into_inner()) += 1;
+		self.0.released.notify_one();
+	}
+}
+
+/// A file read from a tar archive of a container's filesystem
+pub(crate) enum ArchivedFile {
+	File(Vec<u8>),
+	Symlink(PathBuf),
+}
+
+/// Read the first file out of a tar archive, e.g., from `docker cp`
+pub(crate) fn unarchive_file(archive: &[u8]) -> Result<ArchivedFile, DockerError> {
+	let invalid = |x: std::io::Error| DockerError::InvalidOutput(x.to_string());
+	let mut archive = tar::Archive::new(archive);
+
+	for entry in archive.entries().map_err(invalid)? {
+		let mut entry = entry.map_err(invalid)?;
+		let kind = entry.header().entry_type();
+
+		if kind.is_symlink() {
+			let target = entry.link_name().map_err(invalid)?.unwrap_or_default();
+			return Ok(ArchivedFile::Symlink(target.into_owned()));
+		}
+
+		if kind.is_file() {
+			let mut contents = vec![];
+			entry.read_to_end(&mut contents).map_err(invalid)?;
+			return Ok(ArchivedFile::File(contents));
+		}
+	}
+
+	Err(DockerError::InvalidOutput(String::from(
+		"Archive did not contain a file",
+	)))
+}
+
+/// Read the regular files directly inside the directory archived by `docker cp`
+pub(crate) fn unarchive_dir(archive: &[u8]) -> Result<Vec<Vec<u8>>, DockerError> {
+	let invalid = |x: std::io::Error| DockerError::InvalidOutput(x.to_string());
+	let mut archive = tar::Archive::new(archive);
+	let mut files = vec![];
+
+	for entry in archive.entries().map_err(invalid)? {
+		let mut entry = entry.map_err(invalid)?;
+		// Entries are named relative to the directory's parent, e.g., "status.d/libc6"
+		let depth = entry.path().map_err(invalid)?.components().count();
+
+		if entry.header().entry_type().is_file() && depth == 2 {
+			let mut contents = vec![];
+			entry.read_to_end(&mut contents).map_err(invalid)?;
+			files.push(contents);
+		}
+	}
+
+	Ok(files)
+}
+
+/// Read a file using `fetch`, following any symlinks it returns
+///
+/// Link targets are resolved relative to the linking file's directory,
+/// e.g., `/etc/os-release -> ../usr/lib/os-release`.
+pub(crate) fn follow_links(
+	path: &str,
+	mut fetch: impl FnMut(&str) -> Result<ArchivedFile, DockerError>,
+) -> Result<Vec<u8>, DockerError> {
+	let mut path = PathBuf::from(path);
+
+	for _ in 0..16 {
+		match fetch(&path.to_string_lossy())? {
+			ArchivedFile::File(x) => return Ok(x),
+			ArchivedFile::Symlink(x) => {
+				let parent = path.parent().unwrap_or(Path::new("/"));
+				path = normalize(&parent.join(x));
+			}
+		}
+	}
+
+	Err(DockerError::InvalidOutput(format!(
+		"Too many symlinks reading {}",
+		path.display()
+	)))
+}
+
+/// Resolve `.` and `..` in an absolute path without touching the filesystem
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::from("/");
+
+	for component in path.components() {
+		match component {
+			Component::ParentDir => {
+				normalized.pop();
+			}
+			Component::Normal(x) => normalized.push(x),
+			_ => (),
+		}
+	}
+
+	normalized
+}
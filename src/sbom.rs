@@ -0,0 +1,448 @@
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+use crate::application;
+use crate::container::Status;
+use crate::docker::api::encode;
+use crate::package::{Distro, Manager, Package};
+use crate::runtime;
+
+const TOOL: &str = env!("CARGO_PKG_NAME");
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Build a CycloneDX 1.5 SBOM from container statuses
+///
+/// Each container is a component, with its apps and any inventoried
+/// packages as sub-components. EOL dates, failures and errors are
+/// kept as `corrator:` properties.
+///
+/// # Example
+/// ```rust
+/// # use corrator::container::Status;
+/// let bom = corrator::sbom::cyclonedx(&[Status::new(String::from("ubuntu"))]);
+///
+/// assert_eq!(bom["bomFormat"], "CycloneDX");
+/// assert_eq!(bom["components"][0]["name"], "ubuntu");
+/// ```
+pub fn cyclonedx(statuses: &[Status]) -> Value {
+	let components: Vec<Value> = statuses
+		.iter()
+		.map(|container| {
+			let reference = match &container.platform {
+				Some(x) => format!("container:{}@{x}", container.name),
+				None => format!("container:{}", container.name),
+			};
+			let mut children: Vec<Value> = container
+				.apps
+				.iter()
+				.map(|app| {
+					let mut component = json!({
+						"type": "application",
+						"bom-ref": format!("{reference}/app:{}", app.name),
+						"name": app.name,
+						"properties": properties(app_properties(app)),
+					});
+					if let Some(x) = &app.version {
+						component["version"] = json!(x);
+					}
+					component
+				})
+				.collect();
+
+			children.extend(container.inventory.iter().flatten().map(|x| {
+				// Multilib hosts install the same version once per arch
+				let mut package = format!("{reference}/{}:{}@{}", x.manager, x.name, x.version);
+				if let Some(arch) = &x.arch {
+					write!(package, "?arch={arch}").unwrap();
+				}
+
+				json!({
+					"type": "library",
+					"bom-ref": package,
+					"name": x.name,
+					"version": x.version,
+					"purl": purl(x, container.distro.as_ref()),
+				})
+			}));
+
+			let mut component = json!({
+				"type": "container",
+				"bom-ref": reference,
+				"name": display_name(container),
+				"properties": properties(container_properties(container)),
+				"components": children,
+			});
+			if let Some(x) = container.image.as_ref().and_then(|x| x.digest.as_ref()) {
+				component["version"] = json!(x);
+			}
+			component
+		})
+		.collect();
+
+	json!({
+		"bomFormat": "CycloneDX",
+		"specVersion": "1.5",
+		"version": 1,
+		"metadata": {
+			"timestamp": timestamp(),
+			"tools": {
+				"components": [{ "type": "application", "name": TOOL, "version": VERSION }],
+			},
+		},
+		"components": components,
+	})
+}
+
+/// Build an SPDX 2.3 document from container statuses
+///
+/// Each container is a package which the document describes, containing
+/// a package for each app and inventoried package. SPDX has no free-form
+/// properties, so EOL dates, failures and errors become annotations.
+///
+/// # Example
+/// ```rust
+/// # use corrator::container::Status;
+/// let document = corrator::sbom::spdx(&[Status::new(String::from("ubuntu"))]);
+///
+/// assert_eq!(document["spdxVersion"], "SPDX-2.3");
+/// assert_eq!(document["packages"][0]["name"], "ubuntu");
+/// ```
+pub fn spdx(statuses: &[Status]) -> Value {
+	let created = timestamp();
+	let creator = format!("Tool: {TOOL}-{VERSION}");
+	let annotations = |notes: Vec<(&str, String)>| -> Vec<Value> {
+		notes
+			.into_iter()
+			.map(|(name, value)| {
+				json!({
+					"annotationType": "OTHER",
+					"annotator": creator,
+					"annotationDate": created,
+					"comment": format!("{TOOL}:{name}: {value}"),
+				})
+			})
+			.collect()
+	};
+
+	let mut packages = vec![];
+	let mut relationships = vec![];
+	let mut relate = |parent: &str, kind: &str, child: &str| {
+		relationships.push(json!({
+			"spdxElementId": parent,
+			"relationshipType": kind,
+			"relatedSpdxElement": child,
+		}))
+	};
+
+	for (i, container) in statuses.iter().enumerate() {
+		let id = format!("SPDXRef-Container-{i}-{}", spdx_id(&container.name));
+		let mut package = spdx_package(&id, &display_name(container), None);
+		package["primaryPackagePurpose"] = json!("CONTAINER");
+		package["annotations"] = json!(annotations(container_properties(container)));
+		if let Some(x) = container.image.as_ref().and_then(|x| x.digest.as_ref()) {
+			package["versionInfo"] = json!(x);
+		}
+		packages.push(package);
+		relate("SPDXRef-DOCUMENT", "DESCRIBES", &id);
+
+		for (j, app) in container.apps.iter().enumerate() {
+			let app_id = format!("{id}-App-{j}-{}", spdx_id(&app.name));
+			let mut package = spdx_package(&app_id, &app.name, app.version.as_deref());
+			package["primaryPackagePurpose"] = json!("APPLICATION");
+			package["annotations"] = json!(annotations(app_properties(app)));
+			packages.push(package);
+			relate(&id, "CONTAINS", &app_id);
+		}
+
+		for (j, x) in container.inventory.iter().flatten().enumerate() {
+			let package_id = format!("{id}-Package-{j}-{}", spdx_id(&x.name));
+			let mut package = spdx_package(&package_id, &x.name, Some(&x.version));
+			package["primaryPackagePurpose"] = json!("LIBRARY");
+			package["externalRefs"] = json!([{
+				"referenceCategory": "PACKAGE-MANAGER",
+				"referenceType": "purl",
+				"referenceLocator": purl(x, container.distro.as_ref()),
+			}]);
+			packages.push(package);
+			relate(&id, "CONTAINS", &package_id);
+		}
+	}
+
+	json!({
+		"spdxVersion": "SPDX-2.3",
+		"dataLicense": "CC0-1.0",
+		"SPDXID": "SPDXRef-DOCUMENT",
+		"name": TOOL,
+		"documentNamespace": format!("https://spdx.org/spdxdocs/{}", runtime::run_id()),
+		"creationInfo": { "created": created, "creators": [creator] },
+		"packages": packages,
+		"relationships": relationships,
+	})
+}
+
+fn spdx_package(id: &str, name: &str, version: Option<&str>) -> Value {
+	let mut package = json!({
+		"SPDXID": id,
+		"name": name,
+		"downloadLocation": "NOASSERTION",
+		"filesAnalyzed": false,
+	});
+	if let Some(x) = version {
+		package["versionInfo"] = json!(x);
+	}
+	package
+}
+
+/// A container's name, with its platform when it was queried for one, since
+/// each platform is reported separately
+fn display_name(container: &Status) -> String {
+	match &container.platform {
+		Some(x) => format!("{} ({x})", container.name),
+		None => container.name.clone(),
+	}
+}
+
+/// Details of a container worth keeping in an SBOM, by property name
+fn container_properties(container: &Status) -> Vec<(&'static str, String)> {
+	let mut found = vec![];
+	found.extend(container.platform.clone().map(|x| ("platform", x)));
+	found.extend(container.image.as_ref().map(|x| ("image", x.id.clone())));
+	found.extend(container.error.clone().map(|x| ("error", x)));
+	found
+}
+
+/// Details of an app worth keeping in an SBOM, by property name
+fn app_properties(app: &application::Status) -> Vec<(&'static str, String)> {
+	let mut found = vec![];
+	found.extend(app.eol_status.clone().map(|x| ("eol", x)));
+	found.extend(app.check.as_ref().map(|x| match x {
+		application::Check::Pass => ("check", String::from("pass")),
+		application::Check::Fail { reason } => ("check", format!("fail: {reason}")),
+	}));
+	found.extend(app.failure.as_ref().map(|x| ("failure", x.to_string())));
+	found
+}
+
+fn properties(found: Vec<(&str, String)>) -> Vec<Value> {
+	found
+		.into_iter()
+		.map(|(name, value)| json!({ "name": format!("{TOOL}:{name}"), "value": value }))
+		.collect()
+}
+
+/// A package URL for an inventoried package, e.g.,
+/// "pkg:deb/ubuntu/openssl@3.0.2?arch=amd64&distro=ubuntu-22.04"
+///
+/// Names and versions are percent-encoded, with an npm scope kept as the
+/// namespace and pypi names normalized, as the purl spec asks. OS packages
+/// are namespaced by their distribution, when it's known.
+fn purl(package: &Package, distro: Option<&Distro>) -> String {
+	let kind = match package.manager {
+		Manager::Dpkg => "deb",
+		Manager::Apk => "apk",
+		Manager::Rpm => "rpm",
+		Manager::Pip => "pypi",
+		Manager::Npm => "npm",
+		Manager::Gem => "gem",
+	};
+	let name = match package.manager {
+		Manager::Pip => encode(&package.name.to_lowercase().replace('_', "-")),
+		_ => match package.name.split_once('/') {
+			Some((namespace, name)) => format!("{}/{}", encode(namespace), encode(name)),
+			None => encode(&package.name),
+		},
+	};
+
+	let os = matches!(package.manager, Manager::Dpkg | Manager::Apk | Manager::Rpm);
+	let distro = distro.filter(|_| os);
+
+	let mut purl = String::from("pkg:");
+	match distro {
+		Some(x) => write!(purl, "{kind}/{}/", encode(&x.id)),
+		None => write!(purl, "{kind}/"),
+	}
+	.unwrap();
+	write!(purl, "{name}@{}", encode(&package.version)).unwrap();
+
+	// Qualifiers are sorted by key
+	let mut qualifiers = vec![];
+	qualifiers.extend(package.arch.as_ref().map(|x| format!("arch={}", encode(x))));
+	qualifiers.extend(distro.map(|x| match &x.version_id {
+		Some(version) => format!("distro={}-{}", encode(&x.id), encode(version)),
+		None => format!("distro={}", encode(&x.id)),
+	}));
+	if !qualifiers.is_empty() {
+		write!(purl, "?{}", qualifiers.join("&")).unwrap();
+	}
+
+	purl
+}
+
+/// SPDX identifiers may only contain letters, numbers, `.` and `-`
+fn spdx_id(name: &str) -> String {
+	name.chars()
+		.map(|x| match x.is_ascii_alphanumeric() || x == '.' {
+			true => x,
+			false => '-',
+		})
+		.collect()
+}
+
+fn timestamp() -> String {
+	Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::application::{Failure, FailureKind};
+
+	fn statuses() -> Vec<Status> {
+		let mut status = Status::new(String::from("web/api"));
+		status.apps = vec![
+			application::Status::new(
+				String::from("node"),
+				String::from("20.11.0"),
+				Some(String::from("alive")),
+			),
+			application::Status::failed(String::from("bash"), Failure::new(FailureKind::NoMatch)),
+		];
+		status.inventory = Some(vec![Package {
+			name: String::from("express"),
+			version: String::from("4.18.2"),
+			manager: Manager::Npm,
+			arch: None,
+		}]);
+		vec![status]
+	}
+
+	#[test]
+	fn containers_become_cyclonedx_components() {
+		let bom = cyclonedx(&statuses());
+		let children = &bom["components"][0]["components"];
+
+		assert_eq!(children[0]["name"], "node");
+		assert_eq!(children[0]["version"], "20.11.0");
+		assert_eq!(
+			children[0]["properties"],
+			json!([{ "name": "corrator:eol", "value": "alive" }])
+		);
+		assert_eq!(children[1].get("version"), None);
+		assert_eq!(
+			children[1]["properties"][0]["value"],
+			"version regex did not match"
+		);
+		assert_eq!(children[2]["purl"], "pkg:npm/express@4.18.2");
+	}
+
+	#[test]
+	fn containers_become_spdx_packages() {
+		let document = spdx(&statuses());
+		let packages = document["packages"].as_array().unwrap();
+
+		assert_eq!(packages.len(), 4);
+		assert_eq!(packages[0]["SPDXID"], "SPDXRef-Container-0-web-api");
+		assert_eq!(packages[1]["versionInfo"], "20.11.0");
+		assert_eq!(
+			packages[1]["annotations"][0]["comment"],
+			"corrator:eol: alive"
+		);
+		assert_eq!(
+			document["relationships"][0],
+			json!({
+				"spdxElementId": "SPDXRef-DOCUMENT",
+				"relationshipType": "DESCRIBES",
+				"relatedSpdxElement": "SPDXRef-Container-0-web-api",
+			})
+		);
+		assert_eq!(document["relationships"].as_array().unwrap().len(), 4);
+	}
+
+	#[test]
+	fn platforms_are_distinct_components() {
+		let mut amd64 = Status::new(String::from("ubuntu"));
+		amd64.platform = Some(String::from("linux/amd64"));
+		let mut arm64 = Status::new(String::from("ubuntu"));
+		arm64.platform = Some(String::from("linux/arm64"));
+
+		let bom = cyclonedx(&[amd64, arm64]);
+
+		assert_eq!(
+			bom["components"][0]["bom-ref"],
+			"container:ubuntu@linux/amd64"
+		);
+		assert_eq!(
+			bom["components"][1]["bom-ref"],
+			"container:ubuntu@linux/arm64"
+		);
+		assert_eq!(bom["components"][1]["name"], "ubuntu (linux/arm64)");
+	}
+
+	#[test]
+	fn purls_are_encoded() {
+		let package = |name: &str, version: &str, manager| Package {
+			name: String::from(name),
+			version: String::from(version),
+			manager,
+			arch: None,
+		};
+
+		assert_eq!(
+			purl(&package("@types/node", "20.11.0", Manager::Npm), None),
+			"pkg:npm/%40types/node@20.11.0"
+		);
+		assert_eq!(
+			purl(
+				&package("libc6", "1:2.35-0ubuntu3+esm1", Manager::Dpkg),
+				None
+			),
+			"pkg:deb/libc6@1%3A2.35-0ubuntu3%2Besm1"
+		);
+		assert_eq!(
+			purl(&package("Typing_Extensions", "4.9.0", Manager::Pip), None),
+			"pkg:pypi/typing-extensions@4.9.0"
+		);
+	}
+
+	#[test]
+	fn os_purls_name_their_distro() {
+		let distro = Distro {
+			id: String::from("fedora"),
+			version_id: Some(String::from("40")),
+		};
+		let package = |name: &str, manager, arch: Option<&str>| Package {
+			name: String::from(name),
+			version: String::from("2.39-17.fc40"),
+			manager,
+			arch: arch.map(String::from),
+		};
+
+		assert_eq!(
+			purl(&package("glibc", Manager::Rpm, Some("i686")), Some(&distro)),
+			"pkg:rpm/fedora/glibc@2.39-17.fc40?arch=i686&distro=fedora-40"
+		);
+		assert_eq!(
+			purl(&package("requests", Manager::Pip, None), Some(&distro)),
+			"pkg:pypi/requests@2.39-17.fc40"
+		);
+
+		let mut status = Status::new(String::from("fedora"));
+		status.distro = Some(distro);
+		status.inventory = Some(vec![
+			package("glibc", Manager::Rpm, Some("i686")),
+			package("glibc", Manager::Rpm, Some("x86_64")),
+		]);
+		let bom = cyclonedx(&[status]);
+		let children = &bom["components"][0]["components"];
+
+		assert_eq!(
+			children[0]["bom-ref"],
+			"container:fedora/rpm:glibc@2.39-17.fc40?arch=i686"
+		);
+		assert_eq!(
+			children[1]["bom-ref"],
+			"container:fedora/rpm:glibc@2.39-17.fc40?arch=x86_64"
+		);
+	}
+}
@@ -0,0 +1,456 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{cmp::Ordering, error::Error, fmt, str::FromStr};
+
+#[derive(Debug)]
+pub struct ParseVersionError;
+impl Error for ParseVersionError {}
+impl fmt::Display for ParseVersionError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Could not parse version")
+	}
+}
+
+/// An application or package version which can be ordered
+///
+/// Parsing is lenient, so that semver (`1.2.3-rc.1`), shorter or lettered
+/// versions (`22.04`, `1.1.1w`) and Debian versions (`1:3.0.2-0ubuntu1`)
+/// are all accepted. Versions are compared the way dpkg compares them, except
+/// that a semver pre-release sorts before its release.
+///
+/// A suffix after a semver version's `-` is a pre-release if it starts with a
+/// letter, e.g., `-beta.2`. Any other suffix after the last `-` is a revision,
+/// e.g., Debian's `-1ubuntu2` or Alpine's `-r10`.
+///
+/// # Example
+/// ```rust
+/// # use corrator::version::Version;
+/// let parse = |x: &str| x.parse::<Version>().unwrap();
+///
+/// assert!(parse("1.10.0") > parse("1.9.3"));
+/// assert!(parse("1.1.1w") > parse("1.1.1v"));
+/// assert!(parse("2.0.0-rc.1") < parse("2.0.0"));
+/// assert!(parse("1:1.0-1") > parse("2.0-1"));
+/// assert!(parse("3.1.4-r10") > parse("3.1.4-r9"));
+/// assert_eq!(parse("v22.04"), parse("22.04"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Version {
+	pub epoch: u64,
+	pub upstream: String,
+	pub pre: Option<String>,
+	pub revision: Option<String>,
+	raw: String,
+}
+
+impl Version {
+	/// The version as it was found
+	pub fn as_str(&self) -> &str {
+		&self.raw
+	}
+
+	/// Compare against a constraint's bound, which is only as precise as it's written
+	///
+	/// Missing numeric parts count as zeros, and a bound without a revision
+	/// ignores this version's revision.
+	///
+	/// # Example
+	/// ```rust
+	/// # use std::cmp::Ordering;
+	/// # use corrator::version::Version;
+	/// let parse = |x: &str| x.parse::<Version>().unwrap();
+	///
+	/// assert_eq!(parse("2.0.0").cmp_bound(&parse("2.0")), Ordering::Equal);
+	/// assert_eq!(parse("3.0.8-r0").cmp_bound(&parse("3.0.8")), Ordering::Equal);
+	/// assert_eq!(parse("3.0.8-r0").cmp_bound(&parse("3.0.8-r1")), Ordering::Less);
+	/// ```
+	pub fn cmp_bound(&self, bound: &Version) -> Ordering {
+		let parts = |x: &str| x.split('.').count();
+		let length = parts(&self.upstream).max(parts(&bound.upstream));
+		let pad = |x: &str| format!("{x}{}", ".0".repeat(length - parts(x)));
+
+		let version = Version {
+			upstream: pad(&self.upstream),
+			revision: bound.revision.as_ref().and(self.revision.clone()),
+			..self.clone()
+		};
+		let bound = Version {
+			upstream: pad(&bound.upstream),
+			..bound.clone()
+		};
+
+		version.cmp(&bound)
+	}
+}
+
+impl FromStr for Version {
+	type Err = ParseVersionError;
+
+	fn from_str(input: &str) -> Result<Self, Self::Err> {
+		let raw = input.trim();
+		if !raw.contains(|x: char| x.is_ascii_digit()) {
+			return Err(ParseVersionError);
+		}
+
+		let (epoch, rest) = match raw.split_once(':') {
+			Some((x, rest)) if !x.is_empty() && x.chars().all(|x| x.is_ascii_digit()) => {
+				(x.parse().map_err(|_| ParseVersionError)?, rest)
+			}
+			_ => (0, raw),
+		};
+		let rest = rest.strip_prefix(['v', 'V']).unwrap_or(rest);
+
+		// Build metadata never affects ordering, but only semver has it;
+		// elsewhere `+` is part of the version, e.g., `2.35+dfsg`
+		let rest = match rest.split_once('+') {
+			Some((x, _)) if is_semver(x) => x,
+			_ => rest,
+		};
+
+		let (upstream, pre, revision) = match (rest.split_once('-'), rest.rsplit_once('-')) {
+			(Some((x, suffix)), _) if is_semver(x) && is_pre_release(suffix) => {
+				(x, Some(suffix), None)
+			}
+			(_, Some((x, suffix))) if !x.is_empty() => (x, None, Some(suffix)),
+			_ => (rest, None, None),
+		};
+
+		Ok(Self {
+			epoch,
+			upstream: String::from(upstream),
+			pre: pre.map(String::from),
+			revision: revision.map(String::from),
+			raw: String::from(raw),
+		})
+	}
+}
+
+impl Ord for Version {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.epoch
+			.cmp(&other.epoch)
+			.then_with(|| compare_parts(&self.upstream, &other.upstream))
+			.then_with(|| match (&self.pre, &other.pre) {
+				(None, None) => Ordering::Equal,
+				(None, Some(_)) => Ordering::Greater,
+				(Some(_), None) => Ordering::Less,
+				(Some(a), Some(b)) => compare_pre(a, b),
+			})
+			.then_with(|| {
+				let revision = |x: &Self| x.revision.clone().unwrap_or_default();
+				compare_parts(&revision(self), &revision(other))
+			})
+	}
+}
+
+impl PartialOrd for Version {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+// Equal versions may be written differently, e.g., "1.01" and "1.1"
+impl PartialEq for Version {
+	fn eq(&self, other: &Self) -> bool {
+		self.cmp(other) == Ordering::Equal
+	}
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.raw)
+	}
+}
+
+impl Serialize for Version {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&self.raw)
+	}
+}
+
+impl<'de> Deserialize<'de> for Version {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer)?
+			.parse()
+			.map_err(de::Error::custom)
+	}
+}
+
+/// A comparison a version must satisfy, e.g., `>= 1.24`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+	Eq,
+	Ne,
+	Gt,
+	Ge,
+	Lt,
+	Le,
+}
+
+/// A set of comparisons which a version must all satisfy, e.g., `>=1.24, <2`
+///
+/// A version without an operator must match exactly.
+///
+/// # Example
+/// ```rust
+/// # use corrator::version::{Requirement, Version};
+/// let requirement: Requirement = ">=1.24, <2".parse().unwrap();
+/// let matches = |x: &str| requirement.matches(&x.parse::<Version>().unwrap());
+///
+/// assert!(matches("1.24.3"));
+/// assert!(!matches("1.23"));
+/// assert!(!matches("2.0.1"));
+/// assert!(matches("1.24-r3"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Requirement {
+	pub comparisons: Vec<(Op, Version)>,
+	raw: String,
+}
+
+impl Requirement {
+	/// Whether a version satisfies every comparison, as compared by [`Version::cmp_bound`]
+	pub fn matches(&self, version: &Version) -> bool {
+		self.comparisons.iter().all(|(op, x)| {
+			let ordering = version.cmp_bound(x);
+			match op {
+				Op::Eq => ordering.is_eq(),
+				Op::Ne => ordering.is_ne(),
+				Op::Gt => ordering.is_gt(),
+				Op::Ge => ordering.is_ge(),
+				Op::Lt => ordering.is_lt(),
+				Op::Le => ordering.is_le(),
+			}
+		})
+	}
+}
+
+impl FromStr for Requirement {
+	type Err = ParseVersionError;
+
+	fn from_str(input: &str) -> Result<Self, Self::Err> {
+		let operators = [
+			(">=", Op::Ge),
+			("<=", Op::Le),
+			("!=", Op::Ne),
+			("==", Op::Eq),
+			(">", Op::Gt),
+			("<", Op::Lt),
+			("=", Op::Eq),
+		];
+
+		let comparisons = input
+			.split(',')
+			.map(|x| {
+				let x = x.trim();
+				let (op, version) = operators
+					.iter()
+					.find_map(|(prefix, op)| Some((*op, x.strip_prefix(prefix)?)))
+					.unwrap_or((Op::Eq, x));
+
+				Ok((op, version.parse()?))
+			})
+			.collect::<Result<_, _>>()?;
+
+		Ok(Self {
+			comparisons,
+			raw: String::from(input.trim()),
+		})
+	}
+}
+
+impl fmt::Display for Requirement {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.raw)
+	}
+}
+
+impl Serialize for Requirement {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(&self.raw)
+	}
+}
+
+impl<'de> Deserialize<'de> for Requirement {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer)?
+			.parse()
+			.map_err(de::Error::custom)
+	}
+}
+
+/// Whether a semver suffix is a pre-release, e.g., "rc.1", rather than a
+/// package revision, e.g., Alpine's "r0"
+fn is_pre_release(suffix: &str) -> bool {
+	let apk_revision = suffix
+		.strip_prefix('r')
+		.is_some_and(|x| !x.is_empty() && x.chars().all(|x| x.is_ascii_digit()));
+
+	suffix.starts_with(|x: char| x.is_ascii_alphabetic()) && !apk_revision
+}
+
+fn is_semver(input: &str) -> bool {
+	let core = input.split_once('-').map_or(input, |(x, _)| x);
+	let numbers: Vec<&str> = core.split('.').collect();
+
+	numbers.len() == 3
+		&& numbers
+			.iter()
+			.all(|x| !x.is_empty() && x.chars().all(|x| x.is_ascii_digit()))
+}
+
+/// Compare version strings as dpkg does
+///
+/// Runs of digits are compared numerically, and everything else by character,
+/// with letters before other symbols and `~` before even the end of a string.
+fn compare_parts(a: &str, b: &str) -> Ordering {
+	let order = |x: Option<&u8>| match x {
+		None => 0,
+		Some(x) if x.is_ascii_digit() => 0,
+		Some(x) if x.is_ascii_alphabetic() => i32::from(*x),
+		Some(b'~') => -1,
+		Some(x) => i32::from(*x) + 256,
+	};
+	let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+	let non_digit = |x: &[u8]| x.first().is_some_and(|x| !x.is_ascii_digit());
+
+	while !a.is_empty() || !b.is_empty() {
+		while non_digit(a) || non_digit(b) {
+			let ordering = order(a.first()).cmp(&order(b.first()));
+			if ordering != Ordering::Equal {
+				return ordering;
+			}
+			a = a.get(1..).unwrap_or_default();
+			b = b.get(1..).unwrap_or_default();
+		}
+
+		let (x, rest) = split_number(a);
+		a = rest;
+		let (y, rest) = split_number(b);
+		b = rest;
+
+		let ordering = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+		if ordering != Ordering::Equal {
+			return ordering;
+		}
+	}
+
+	Ordering::Equal
+}
+
+/// Split a leading number, without its leading zeros, from the rest of a string
+fn split_number(input: &[u8]) -> (&[u8], &[u8]) {
+	let (number, rest) = input.split_at(input.iter().take_while(|x| x.is_ascii_digit()).count());
+	let zeros = number.iter().take_while(|x| **x == b'0').count();
+
+	(&number[zeros..], rest)
+}
+
+/// Compare semver pre-releases, e.g., "alpha.1" < "alpha.beta" < "beta"
+fn compare_pre(a: &str, b: &str) -> Ordering {
+	let mut a = a.split('.');
+	let mut b = b.split('.');
+
+	loop {
+		let ordering = match (a.next(), b.next()) {
+			(None, None) => return Ordering::Equal,
+			(None, Some(_)) => Ordering::Less,
+			(Some(_), None) => Ordering::Greater,
+			(Some(x), Some(y)) => match (x.parse::<u64>(), y.parse::<u64>()) {
+				(Ok(x), Ok(y)) => x.cmp(&y),
+				(Ok(_), Err(_)) => Ordering::Less,
+				(Err(_), Ok(_)) => Ordering::Greater,
+				(Err(_), Err(_)) => x.cmp(y),
+			},
+		};
+
+		if ordering != Ordering::Equal {
+			return ordering;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(input: &str) -> Version {
+		input.parse().unwrap()
+	}
+
+	#[test]
+	fn parses_components() {
+		let version = parse("1:3.0.2-0ubuntu1.15");
+		assert_eq!(version.epoch, 1);
+		assert_eq!(version.upstream, "3.0.2");
+		assert_eq!(version.revision.as_deref(), Some("0ubuntu1.15"));
+
+		let version = parse("2.0.0-rc.1+build.5");
+		assert_eq!(version.upstream, "2.0.0");
+		assert_eq!(version.pre.as_deref(), Some("rc.1"));
+		assert_eq!(version.to_string(), "2.0.0-rc.1+build.5");
+
+		assert_eq!(parse("2.35+dfsg-1").upstream, "2.35+dfsg");
+		assert!("unknown".parse::<Version>().is_err());
+	}
+
+	#[test]
+	fn orders_versions() {
+		let ordered = [
+			"0.9",
+			"1.0~rc1",
+			"1.0",
+			"1.0.0-alpha",
+			"1.0.0-alpha.1",
+			"1.0.0-beta",
+			"1.0.0",
+			"1.0.0-1",
+			"1.0.0-1ubuntu2",
+			"1.0.0-2",
+			"1.1.1",
+			"1.1.1a",
+			"1.1.1w",
+			"1.2",
+			"1.10",
+			"22.04",
+			"1:0.1",
+		];
+
+		for pair in ordered.windows(2) {
+			assert!(parse(pair[0]) < parse(pair[1]), "{} < {}", pair[0], pair[1]);
+		}
+		assert_eq!(parse("1.01"), parse("1.1"));
+		assert_eq!(parse("1.2.3+build.1"), parse("1.2.3+build.2"));
+
+		let apk = [
+			"3.0.8",
+			"3.0.8-r0",
+			"3.0.8-r1",
+			"3.0.8-r9",
+			"3.0.8-r10",
+			"3.1.4-r5",
+		];
+		for pair in apk.windows(2) {
+			assert!(parse(pair[0]) < parse(pair[1]), "{} < {}", pair[0], pair[1]);
+		}
+		assert_eq!(parse("3.1.4-r10").revision.as_deref(), Some("r10"));
+		assert_eq!(parse("2.0-beta").revision.as_deref(), Some("beta"));
+	}
+
+	#[test]
+	fn requirements_pad_missing_parts() {
+		let matches = |requirement: &str, version: &str| {
+			requirement
+				.parse::<Requirement>()
+				.unwrap()
+				.matches(&parse(version))
+		};
+
+		assert!(matches("<=2.0", "2.0.0"));
+		assert!(matches("3", "3.0"));
+		assert!(matches(">=3.0.8", "3.0.8-r0"));
+		assert!(!matches(">3.0.8-r0", "3.0.8-r0"));
+		assert!(!matches("<2", "2.0.1"));
+	}
+}